@@ -1,104 +1,200 @@
-use core::panic;
+use std::{path::Path, process::ExitCode};
 
 use clap::Parser;
-use stack::{cli::Cli, config::Config, go};
+use stack::{
+    category::CategoryRef,
+    cli::{check_path, split_target, Cli, Commands, ConfigCommands, Format},
+    config::{resolve_root, Config, Language, CONFIG_FILE, CONFIG_VERSION},
+    fs::{DiskFs, Fs},
+    go, inventory,
+    plan::Plan,
+    project::{self, Rollback},
+    rust::InitOptions,
+    toolchain::Toolchain,
+    Error,
+};
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
 
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            if let Error::FileExists(_) = e {
+                eprintln!("Use --force to overwrite it");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> stack::Result<()> {
+    let toolchain = Toolchain::new(cli.verbose);
+
     match cli.command {
-        stack::cli::Commands::Init { name, go, rust } => {
-            let c = Config::new(name.clone(), if go {
-                stack::config::Language::Go
-            } else if rust {
-                stack::config::Language::Rust
-            } else {
-                panic!("You must specify a language")
+        Commands::Init { name, go, here, dependencies, cargo_add, .. } => {
+            let cwd = std::env::current_dir()?;
+            let dir = if here { cwd } else { cwd.join(project::dir_name(&name)) };
+            project::check_init_target(&dir)?;
+
+            let fs = DiskFs::new(&dir);
+            let language = if go { Language::Go } else { Language::Rust };
+            let c = Config::new(name.clone(), language.clone());
+
+            let mut plan = Plan::new();
+            plan.create(CONFIG_FILE, c.to_yaml()?);
+            plan.extend(match language {
+                Language::Go => go::plan_init(&c),
+                Language::Rust => {
+                    let mut options = InitOptions { cargo_add, ..Default::default() };
+                    for (dependency, version) in &dependencies {
+                        options.set_version(dependency, version);
+                    }
+                    stack::rust::plan_init(&c, &options)?
+                }
             });
 
-            match c.write() {
-                Ok(()) => println!("{} config wrote", name),
-                Err(e) => println!("Error: {}", e),
+            if cli.dry_run {
+                if !here {
+                    println!("mkdir   {}", dir.display());
+                }
+                print!("{}", plan.render(&fs, cli.diff)?);
+                return Ok(());
             }
 
-            if go {
-                match stack::go::init_go_mod(&name).await {
-                    Ok(()) => println!("Go mod initialized"),
-                    Err(e) => println!("Error: {}", e),
+            let rollback = Rollback::prepare(&dir)?;
+            if let Err(e) = plan.apply(&fs, &toolchain.configured(&c.toolchain)).await {
+                if let Err(undo) = rollback.undo() {
+                    eprintln!("Failed to roll back {}: {}", dir.display(), undo);
                 }
+                return Err(e);
             }
 
-            if rust {
-                match stack::rust::init_cargo(&name).await {
-                    Ok(()) => println!("Cargo initialized"),
-                    Err(e) => println!("Error: {}", e),
-                }
+            match language {
+                Language::Go => println!("{} initialized as a Go project", name),
+                Language::Rust => println!("{} initialized as a Rust project", name),
             }
         }
-        stack::cli::Commands::Generate {
-            path,
-            name,
-            protocol,
-            message,
-            contract,
-            model,
-            service,
-            mediator,
-            aggregator,
-            handler,
-            adapter,
-            server,
-            assembler,
-        } => {
-            let cfg = match Config::read() {
-                Ok(c) => c,
-                Err(e) => {
-                    println!("Error: {}", e);
-                    return;
-                }
+        Commands::Generate { category, path, name, force } => {
+            let (path, name) = split_target(&path, name.as_deref())?;
+            let build = |planners: &Planners, fs: &dyn Fs, cfg: &Config, category: &CategoryRef| {
+                (planners.generate)(fs, cfg, &name, &path, category, force)
             };
 
-            let category = if protocol {
-                stack::category::Category::Protocol
-            } else if message {
-                stack::category::Category::Message
-            } else if contract {
-                stack::category::Category::Contract
-            } else if model {
-                stack::category::Category::Model
-            } else if service {
-                stack::category::Category::Service
-            } else if mediator {
-                stack::category::Category::Mediator
-            } else if aggregator {
-                stack::category::Category::Aggregator
-            } else if handler {
-                stack::category::Category::Handler
-            } else if adapter {
-                stack::category::Category::Adapter
-            } else if server {
-                stack::category::Category::Server
-            } else if assembler {
-                stack::category::Category::Assembler
-            } else {
-                panic!("You must specify a category")
+            if let Some(category) = change_component(cli.root.as_deref(), (cli.dry_run, cli.diff), toolchain, &category, build).await? {
+                println!("{} {} generated", category, name);
+            }
+        }
+        Commands::Remove { category, path, name } => {
+            let (path, name) = split_target(&path, name.as_deref())?;
+            let build = |planners: &Planners, fs: &dyn Fs, cfg: &Config, category: &CategoryRef| {
+                (planners.remove)(fs, cfg, &name, &path, category)
             };
 
-            match cfg.language {
-                stack::config::Language::Go => {
-                    match go::generate_file(name.as_str(), path.as_str(), cfg.name.as_str(), &category).await {
-                        Ok(()) => println!("{} {} generated", &category, name),
-                        Err(e) => println!("Error: {}", e),
-                    }
-                }
-                stack::config::Language::Rust => {
-                    match stack::rust::generate_file(name.as_str(), path.as_str(), cfg.name.as_str(), &category).await {
-                        Ok(()) => println!("{} {} generated", &category, name),
-                        Err(e) => println!("Error: {}", e),
-                    }
-                }
+            if let Some(category) = change_component(cli.root.as_deref(), (cli.dry_run, cli.diff), toolchain, &category, build).await? {
+                println!("{} {} removed", category, name);
+            }
+        }
+        Commands::Rename { category, path, new_name } => {
+            let (path, name) = split_target(&path, None)?;
+            let build = |planners: &Planners, fs: &dyn Fs, cfg: &Config, category: &CategoryRef| {
+                (planners.relocate)(fs, cfg, category, (&path, &name), (&path, &new_name))
+            };
+
+            if let Some(category) = change_component(cli.root.as_deref(), (cli.dry_run, cli.diff), toolchain, &category, build).await? {
+                println!("{} {} renamed to {}", category, name, new_name);
+            }
+        }
+        Commands::Move { category, path, destination } => {
+            let (path, name) = split_target(&path, None)?;
+            check_path(&destination)?;
+            let destination = destination.trim_end_matches('/');
+            let build = |planners: &Planners, fs: &dyn Fs, cfg: &Config, category: &CategoryRef| {
+                (planners.relocate)(fs, cfg, category, (&path, &name), (destination, &name))
+            };
+
+            if let Some(category) = change_component(cli.root.as_deref(), (cli.dry_run, cli.diff), toolchain, &category, build).await? {
+                println!("{} {} moved to {}", category, name, destination);
+            }
+        }
+        Commands::List { category, format } => {
+            let fs = DiskFs::new(resolve_root(cli.root.as_deref())?);
+            let cfg = Config::read_from(&fs)?;
+
+            let category = category.map(|c| cfg.resolve_category(&c)).transpose()?;
+            let components = inventory::list(&fs, &cfg, category.as_ref())?;
+
+            match format {
+                Format::Table => print!("{}", inventory::render_table(&components)),
+                Format::Json => println!("{}", inventory::render_json(&components)?),
             }
         }
+        Commands::Config { command: ConfigCommands::Migrate } => {
+            let fs = DiskFs::new(resolve_root(cli.root.as_deref())?);
+            let plan = Config::plan_migrate(&fs)?;
+
+            if plan.operations().is_empty() {
+                println!("{} is already at version {}", CONFIG_FILE, CONFIG_VERSION);
+                return Ok(());
+            }
+
+            if cli.dry_run {
+                print!("{}", plan.render(&fs, cli.diff)?);
+                return Ok(());
+            }
+
+            plan.apply(&fs, &toolchain).await?;
+            println!("{} migrated to version {}", CONFIG_FILE, CONFIG_VERSION);
+        }
     }
-}
\ No newline at end of file
+
+    Ok(())
+}
+
+/// The planners of one language, so component commands do not have to match on it.
+struct Planners {
+    generate: fn(&dyn Fs, &Config, &str, &str, &CategoryRef, bool) -> stack::Result<Plan>,
+    remove: fn(&dyn Fs, &Config, &str, &str, &CategoryRef) -> stack::Result<Plan>,
+    relocate: fn(&dyn Fs, &Config, &CategoryRef, Location, Location) -> stack::Result<Plan>,
+}
+
+/// The path of a component inside its category folder, and its name.
+type Location<'a> = (&'a str, &'a str);
+
+const GO_PLANNERS: Planners = Planners { generate: go::plan_generate, remove: go::plan_remove, relocate: go::plan_relocate };
+
+const RUST_PLANNERS: Planners = Planners {
+    generate: stack::rust::plan_generate,
+    remove: stack::rust::plan_remove,
+    relocate: stack::rust::plan_relocate,
+};
+
+/// Reads the config of the project at `root`, resolves `category` and builds a plan with the planners of the project's language.
+/// The plan is printed on a dry run, with a diff if asked, otherwise applied with the configured toolchain and the category returned.
+async fn change_component(
+    root: Option<&Path>,
+    (dry_run, diff): (bool, bool),
+    toolchain: Toolchain,
+    category: &str,
+    build: impl FnOnce(&Planners, &dyn Fs, &Config, &CategoryRef) -> stack::Result<Plan>,
+) -> stack::Result<Option<CategoryRef>> {
+    let fs = DiskFs::new(resolve_root(root)?);
+    let cfg = Config::read_from(&fs)?;
+    let category = cfg.resolve_category(category)?;
+
+    let planners = match cfg.language {
+        Language::Go => &GO_PLANNERS,
+        Language::Rust => &RUST_PLANNERS,
+    };
+    let plan = build(planners, &fs, &cfg, &category)?;
+
+    if dry_run {
+        print!("{}", plan.render(&fs, diff)?);
+        return Ok(None);
+    }
+
+    plan.apply(&fs, &toolchain.configured(&cfg.toolchain)).await?;
+    Ok(Some(category))
+}
@@ -1,19 +1,34 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Category {
+    /// A component that provides some functionality to other components
     Service,
+    /// A component that mediates between services
     Mediator,
+    /// A component that aggregates data from services
     Aggregator,
+    /// A component that handles business logic
     Handler,
+    /// A component that adapts data from server to handler
     Adapter,
+    /// A component that listens for requests and sends responses to clients
     Server,
+    /// A component to validate data, data transmission, or data transformation
     Protocol,
+    /// A data structure that represents a message between serveral components
     Message,
+    /// A component that holds the configuration and global state of the app
     State,
+    /// A component that defines a contract (interface / trait) for handlers
     Contract,
+    /// A data structure that represents a transferable object
     Model,
+    /// A component that assembles data from services. Just executable code
     Assembler,
 }
 
@@ -34,4 +49,60 @@ impl Display for Category {
             Category::Assembler => write!(f, "assembler"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseCategoryError(pub String);
+
+impl Display for ParseCategoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected = Category::value_variants().iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        write!(f, "unknown category '{}', expected one of: {}", self.0, expected.join(", "))
+    }
+}
+
+impl std::error::Error for ParseCategoryError {}
+
+impl FromStr for Category {
+    type Err = ParseCategoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Category as ValueEnum>::from_str(s, true).map_err(|_| ParseCategoryError(s.to_string()))
+    }
+}
+
+/// The kind of code a custom category generates.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A struct with a constructor
+    Struct,
+    /// A trait, or an interface in Go
+    Trait,
+    /// An enum, or a set of typed constants in Go
+    Enum,
+    /// An executable
+    Binary,
+}
+
+/// A category resolved against a project: a built-in one, or a custom one declared in `stack.yaml`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum CategoryRef {
+    Builtin(Category),
+    Custom(String),
+}
+
+impl Display for CategoryRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CategoryRef::Builtin(category) => write!(f, "{}", category),
+            CategoryRef::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<Category> for CategoryRef {
+    fn from(category: Category) -> Self {
+        CategoryRef::Builtin(category)
+    }
+}
@@ -1,8 +1,29 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+
+use crate::Error;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
+    /// Stream the output of cargo and go while they run
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Print the planned file operations without changing anything
+    #[arg(short = 'n', long, global = true)]
+    pub dry_run: bool,
+
+    /// Show a unified diff of every planned file change (requires --dry-run)
+    #[arg(long, global = true, requires = "dry_run")]
+    pub diff: bool,
+
+    /// The root of the stack project. Defaults to the nearest directory containing stack.yaml.
+    /// Not used by init, which works relative to the current directory
+    #[arg(long, global = true, env = "STACK_ROOT")]
+    pub root: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -10,6 +31,7 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Initialize a new stack project
+    #[command(group(ArgGroup::new("language").required(true).args(["go", "rust"])))]
     Init {
         /// A name for the project
         name: String,
@@ -21,57 +43,167 @@ pub enum Commands {
         /// The language to use for the project (Rust)
         #[arg(short, long)]
         rust: bool,
+
+        /// Initialize in the current directory instead of creating a directory named after the project
+        #[arg(long)]
+        here: bool,
+
+        /// Set the version requirement of a Rust dependency, or add one (e.g. --dep tokio=1.38, --dep tokio==1.38.0). Can be repeated
+        #[arg(long = "dep", value_name = "NAME=VERSION", value_parser = parse_dependency, conflicts_with = "go")]
+        dependencies: Vec<(String, String)>,
+
+        /// Add the dependencies of a Rust project with `cargo add`, which needs registry access
+        #[arg(long, conflicts_with = "go")]
+        cargo_add: bool,
     },
     /// Generate a new stack component
     Generate {
-        /// The path to the component to generate. Must use '/' as a separator
+        /// The category of the component to generate: service, mediator, aggregator, handler, adapter,
+        /// server, protocol, message, state, contract, model, assembler, or a category declared in stack.yaml
+        category: String,
+
+        /// The path to the component to generate. Must use '/' as a separator.
+        /// When NAME is omitted, the last segment is used as the name (e.g. billing/Invoice)
         path: String,
 
         /// The name of the component to generate. Recommended to use PascalCase
-        name: String,
+        name: Option<String>,
 
-        /// A component to validate data, data transmission, or data transformation
+        /// Overwrite the component if it already exists
         #[arg(short, long)]
-        protocol: bool,
+        force: bool,
+    },
+    /// Remove a stack component and unregister it
+    Remove {
+        /// The category of the component to remove
+        category: String,
 
-        /// A data structure that represents a message between serveral components
-        #[arg(short = 'g', long)]
-        message: bool,
+        /// The path to the component to remove. Must use '/' as a separator.
+        /// When NAME is omitted, the last segment is used as the name (e.g. billing/Invoice)
+        path: String,
 
-        /// A component that defines a contract (interface / trait) for handlers
-        #[arg(short, long)]
-        contract: bool,
+        /// The name of the component to remove
+        name: Option<String>,
+    },
+    /// Rename a stack component and update the references to it
+    Rename {
+        /// The category of the component to rename
+        category: String,
+
+        /// The component to rename as its path and name, '/'-separated (e.g. billing/Invoice)
+        path: String,
 
-        /// A data structure that represents a transferable object
-        #[arg(short = 'd', long)]
-        model: bool,
+        /// The new name of the component
+        new_name: String,
+    },
+    /// Move a stack component to another path of its category and update the references to it
+    Move {
+        /// The category of the component to move
+        category: String,
 
-        /// A component that provides some functionality to other components
-        #[arg(short, long)]
-        service: bool,
+        /// The component to move as its path and name, '/'-separated (e.g. billing/Invoice)
+        path: String,
 
-        /// A component that mediates between services
+        /// The path to move the component to, '/'-separated. Use an empty string for the category folder
+        destination: String,
+    },
+    /// List the components of the project
+    List {
+        /// Only list components of this category
         #[arg(short, long)]
-        mediator: bool,
+        category: Option<String>,
 
-        /// A component that aggregates data from services
-        #[arg(short = 'r', long)]
-        aggregator: bool,
+        /// The output format
+        #[arg(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Manage stack.yaml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
 
-        /// A component that handles business logic
-        #[arg(short = 'n', long)]
-        handler: bool,
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Upgrade stack.yaml to the current schema version
+    Migrate,
+}
 
-        /// A component that adapts data from server to handler
-        #[arg(short, long)]
-        adapter: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns for reading
+    Table,
+    /// A JSON array for scripts
+    Json,
+}
 
-        /// A component that listens for requests and sends responses to clients
-        #[arg(short = 'v', long)]
-        server: bool,
+fn parse_dependency(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, version)) if !name.trim().is_empty() && !version.trim().is_empty() => {
+            Ok((name.trim().to_string(), version.trim().to_string()))
+        }
+        _ => Err(format!("expected NAME=VERSION, got '{}'", s)),
+    }
+}
+
+/// Splits a `path` and an optional `name` into the component's directory and name.
+/// Without a name, the last segment of `path` is taken as the name.
+pub fn split_target(path: &str, name: Option<&str>) -> crate::Result<(String, String)> {
+    check_path(path)?;
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match name {
+        Some(name) => (path, name),
+        None => path.rsplit_once('/').unwrap_or(("", path)),
+    };
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(Error::InvalidPath(name.to_string()));
+    }
+
+    Ok((dir.to_string(), name.to_string()))
+}
+
+/// Checks that a component path given on the command line stays inside its category folder:
+/// it must be relative, without empty, `.` or `..` segments. A trailing `/` is allowed.
+pub fn check_path(path: &str) -> crate::Result<()> {
+    let trimmed = path.trim_end_matches('/');
+    let escapes = path.starts_with('/')
+        || std::path::Path::new(path).is_absolute()
+        || path.contains('\\')
+        || (!trimmed.is_empty() && trimmed.split('/').any(|d| d.is_empty() || d == "." || d == ".."));
+
+    match escapes {
+        true => Err(Error::InvalidPath(path.to_string())),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dependency_keeps_the_version_requirement() {
+        assert_eq!(parse_dependency("tokio=1.38").unwrap(), ("tokio".to_string(), "1.38".to_string()));
+        assert_eq!(parse_dependency("tokio==1.38.0").unwrap(), ("tokio".to_string(), "=1.38.0".to_string()));
+        assert!(parse_dependency("tokio=").is_err());
+    }
+
+    #[test]
+    fn split_target_takes_the_last_segment_as_name() {
+        assert_eq!(split_target("billing/Invoice", None).unwrap(), ("billing".to_string(), "Invoice".to_string()));
+        assert_eq!(split_target("Invoice", None).unwrap(), (String::new(), "Invoice".to_string()));
+        assert_eq!(split_target("billing/", Some("Invoice")).unwrap(), ("billing".to_string(), "Invoice".to_string()));
+    }
 
-        /// A component that assembles data from services. Just executable code
-        #[arg(short = 'b', long)]
-        assembler: bool,
+    #[test]
+    fn split_target_keeps_paths_inside_the_category() {
+        for path in ["../../../tmp/Escape", "/tmp/Escape", "billing//Invoice", "./Invoice", "billing/..", "a\\..\\Escape"] {
+            assert!(matches!(split_target(path, None), Err(Error::InvalidPath(_))), "{}", path);
+        }
+        assert!(matches!(split_target("billing", Some("..")), Err(Error::InvalidPath(_))));
+        assert!(check_path("").is_ok());
+        assert!(check_path("accounting/").is_ok());
+        assert!(check_path("../accounting").is_err());
     }
 }
@@ -1,32 +1,305 @@
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+
 use::serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use clap::ValueEnum;
+
+use crate::{
+    category::{Category, CategoryRef, Kind},
+    fs::{DiskFs, Fs},
+    go,
+    plan::Plan,
+    rust,
+    strfmt::is_identifier,
+    template::{template_path, Template},
+    Error, Result,
+};
 
 pub const CONFIG_FILE: &str = "stack.yaml";
 
+/// The schema version written by this version of stk.
+pub const CONFIG_VERSION: u64 = 1;
+
+/// Upgrades a config from the version at its index to the next one.
+type Migration = fn(&mut Mapping);
+
+const MIGRATIONS: &[Migration] = &[
+    // 0 -> 1: configs written before versioning only had `name` and `language`.
+    |_| {},
+];
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    pub version: u64,
     pub name: String,
     pub language: Language,
+    /// Folders overriding the default location of a category, relative to the project root
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub layout: BTreeMap<Category, String>,
+    /// Categories declared by the project in addition to the built-in ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub categories: BTreeMap<String, CustomCategory>,
+    /// Binaries to run instead of the `cargo` and `go` found on the PATH
+    #[serde(default, skip_serializing_if = "ToolchainConfig::is_empty")]
+    pub toolchain: ToolchainConfig,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ToolchainConfig {
+    /// The `cargo` binary. Paths with a directory are relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo: Option<PathBuf>,
+    /// The `go` binary. Paths with a directory are relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub go: Option<PathBuf>,
+}
+
+impl ToolchainConfig {
+    pub fn is_empty(&self) -> bool {
+        self.cargo.is_none() && self.go.is_none()
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CustomCategory {
+    /// The folder components are generated in, relative to the project root
+    pub folder: String,
+    /// The kind of code to generate when no template is found
+    pub kind: Kind,
+    /// A template overriding `.stack/templates/<language>/<category>.tmpl`, relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Another name to use on the command line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
 }
 
 impl Config {
     pub fn new(name: String, language: Language) -> Self {
-        Self { name, language }
+        Self {
+            version: CONFIG_VERSION,
+            name,
+            language,
+            layout: BTreeMap::new(),
+            categories: BTreeMap::new(),
+            toolchain: ToolchainConfig::default(),
+        }
+    }
+
+    /// Every category of the project, built-in ones first.
+    pub fn categories(&self) -> Vec<CategoryRef> {
+        let builtin = Category::value_variants().iter().map(|c| CategoryRef::Builtin(*c));
+        let custom = self.categories.keys().map(|name| CategoryRef::Custom(name.clone()));
+        builtin.chain(custom).collect()
+    }
+
+    /// Finds a built-in or custom category by name or alias.
+    pub fn resolve_category(&self, name: &str) -> Result<CategoryRef> {
+        if let Ok(category) = name.parse::<Category>() {
+            return Ok(category.into());
+        }
+
+        for (custom, def) in &self.categories {
+            if custom == name || def.alias.as_deref() == Some(name) {
+                return Ok(CategoryRef::Custom(custom.clone()));
+            }
+        }
+
+        Err(Error::UnknownCategory {
+            name: name.to_string(),
+            expected: self.categories().iter().map(|c| c.to_string()).collect(),
+        })
+    }
+
+    /// The folder components of `category` are generated in.
+    pub fn folder(&self, category: &CategoryRef) -> String {
+        let folder = match category {
+            CategoryRef::Builtin(category) => match self.layout.get(category) {
+                Some(folder) => folder.as_str(),
+                None => match self.language {
+                    Language::Go => go::default_folder(category),
+                    Language::Rust => rust::default_folder(category),
+                },
+            },
+            CategoryRef::Custom(name) => self.custom(name).folder.as_str(),
+        };
+
+        folder.trim_end_matches('/').to_string()
+    }
+
+    /// Whether components of `category` are executables rather than library code.
+    pub fn is_binary(&self, category: &CategoryRef) -> bool {
+        match category {
+            CategoryRef::Builtin(category) => *category == Category::Assembler,
+            CategoryRef::Custom(name) => self.custom(name).kind == Kind::Binary,
+        }
     }
 
-    pub fn write(&self) -> Result<(), std::io::Error> {
-        let file = std::fs::File::create(CONFIG_FILE)?;
-        match serde_yaml::to_writer(file, self) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())),
+    /// Loads the template of `category`: the project's own, or the built-in one for its category or kind.
+    pub fn template(&self, fs: &dyn Fs, category: &CategoryRef) -> Result<Template> {
+        let builtin = |category: &Category| match self.language {
+            Language::Go => go::builtin_template(category),
+            Language::Rust => rust::builtin_template(category),
+        };
+        let kind_template = |kind: Kind| match self.language {
+            Language::Go => go::kind_template(kind),
+            Language::Rust => rust::kind_template(kind),
+        };
+
+        match category {
+            CategoryRef::Builtin(c) => Template::load(fs, &template_path(&self.language, &c.to_string()), Some(builtin(c))),
+            CategoryRef::Custom(name) => {
+                let custom = self.custom(name);
+                match &custom.template {
+                    Some(path) => Template::load(fs, Path::new(path), None),
+                    None => Template::load(fs, &template_path(&self.language, name), Some(kind_template(custom.kind))),
+                }
+            }
         }
     }
 
-    pub fn read() -> Result<Self, std::io::Error> {
-        let file = std::fs::File::open(CONFIG_FILE)?;
-        match serde_yaml::from_reader(file) {
-            Ok(c) => Ok(c),
-            Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())),
+    fn custom(&self, name: &str) -> &CustomCategory {
+        self.categories.get(name).expect("custom categories are resolved from the config")
+    }
+
+    /// Checks that folders stay inside the project and that custom categories can be told apart
+    /// from each other and from the built-in ones.
+    fn validate(&self) -> Result<()> {
+        for (category, folder) in &self.layout {
+            check_relative(&format!("layout folder of {}", category), folder)?;
+        }
+        for (name, def) in &self.categories {
+            check_relative(&format!("folder of category {}", name), &def.folder)?;
+            if let Some(template) = &def.template {
+                check_relative(&format!("template of category {}", name), template)?;
+            }
+        }
+
+        let mut names: Vec<&str> = Vec::new();
+        for (name, def) in &self.categories {
+            for name in std::iter::once(name.as_str()).chain(def.alias.as_deref()) {
+                if !is_identifier(name) {
+                    return Err(Error::InvalidConfig(format!("category name '{}' is not a valid identifier", name)));
+                }
+                if name.parse::<Category>().is_ok() {
+                    return Err(Error::InvalidConfig(format!("category '{}' conflicts with a built-in category", name)));
+                }
+                if names.contains(&name) {
+                    return Err(Error::InvalidConfig(format!("category '{}' is declared more than once", name)));
+                }
+                names.push(name);
+            }
         }
+
+        Ok(())
+    }
+
+    /// Writes the config to `stack.yaml` in the project `root`.
+    pub fn write(&self, root: &Path) -> Result<()> {
+        std::fs::write(root.join(CONFIG_FILE), self.to_yaml()?)?;
+        Ok(())
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(Error::ConfigSerialize)
+    }
+
+    /// Reads the config of the project at `root`.
+    pub fn read(root: &Path) -> Result<Self> {
+        Self::read_from(&DiskFs::new(root))
+    }
+
+    /// Reads the config at the root of `fs`, upgrading it in memory if it uses an older schema.
+    pub fn read_from(fs: &dyn Fs) -> Result<Self> {
+        let (config, _) = Self::read_versioned(fs)?;
+        Ok(config)
+    }
+
+    /// Reads the config like [`Config::read_from`] and also returns the schema version found in the file.
+    pub fn read_versioned(fs: &dyn Fs) -> Result<(Self, u64)> {
+        let data = match fs.read_to_string(Path::new(CONFIG_FILE)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let dir = fs.root().map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+                return Err(Error::ConfigNotFound { dir, searched_parents: false });
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut mapping: Mapping = serde_yaml::from_str(&data).map_err(Error::ConfigParse)?;
+        let version = migrate(&mut mapping)?;
+        let config: Self = serde_yaml::from_value(Value::Mapping(mapping)).map_err(Error::ConfigParse)?;
+        config.validate()?;
+
+        Ok((config, version))
+    }
+
+    /// Plans rewriting `stack.yaml` with the current schema. The plan is empty if the file is up to date.
+    pub fn plan_migrate(fs: &dyn Fs) -> Result<Plan> {
+        let (config, version) = Self::read_versioned(fs)?;
+
+        let mut plan = Plan::new();
+        if version < CONFIG_VERSION {
+            plan.create(CONFIG_FILE, config.to_yaml()?);
+        }
+
+        Ok(plan)
+    }
+}
+
+/// Checks that a path from the config is relative to the project root and cannot leave it.
+fn check_relative(what: &str, path: &str) -> Result<()> {
+    let inside = !path.is_empty()
+        && Path::new(path).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    match inside {
+        true => Ok(()),
+        false => Err(Error::InvalidConfig(format!("{} '{}' must be a relative path inside the project", what, path))),
+    }
+}
+
+/// Runs every migration the config needs and returns the version it was written with.
+fn migrate(mapping: &mut Mapping) -> Result<u64> {
+    let version = match mapping.get("version") {
+        None => 0,
+        Some(v) => v.as_u64().ok_or_else(|| Error::InvalidConfigVersion(format!("{:?}", v)))?,
+    };
+
+    if version > CONFIG_VERSION {
+        return Err(Error::InvalidConfigVersion(version.to_string()));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(mapping);
+        mapping.insert(Value::from("version"), Value::from(from as u64 + 1));
+    }
+
+    Ok(version)
+}
+
+/// Finds the project root by walking up from `start` to the first directory containing `stack.yaml`.
+pub fn find_root(start: &Path) -> Result<PathBuf> {
+    for dir in start.ancestors() {
+        if dir.join(CONFIG_FILE).is_file() {
+            return Ok(dir.to_path_buf());
+        }
+    }
+
+    Err(Error::ConfigNotFound { dir: start.to_path_buf(), searched_parents: true })
+}
+
+/// Resolves the project root from an explicit `root`, or by searching upward from the current directory.
+pub fn resolve_root(root: Option<&Path>) -> Result<PathBuf> {
+    match root {
+        Some(root) if root.join(CONFIG_FILE).is_file() => Ok(root.to_path_buf()),
+        Some(root) => Err(Error::ConfigNotFound { dir: root.to_path_buf(), searched_parents: false }),
+        None => find_root(&std::env::current_dir()?),
     }
 }
 
@@ -34,4 +307,109 @@ impl Config {
 pub enum Language {
     Go,
     Rust,
-}
\ No newline at end of file
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Language::Go => write!(f, "go"),
+            Language::Rust => write!(f, "rust"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::MemoryFs;
+
+    #[test]
+    fn missing_config_reports_the_root() {
+        let error = Config::read_from(&MemoryFs::new()).unwrap_err();
+        assert_eq!(error.to_string(), "no stack.yaml found in .");
+
+        let root = std::env::temp_dir().join(format!("stack-missing-config-{}", std::process::id()));
+        let error = Config::read(&root).unwrap_err();
+        assert_eq!(error.to_string(), format!("no stack.yaml found in {}", root.display()));
+
+        let error = find_root(&root).unwrap_err();
+        assert!(matches!(error, Error::ConfigNotFound { searched_parents: true, .. }), "{}", error);
+    }
+
+    #[test]
+    fn write_and_read_from_the_root() {
+        let root = std::env::temp_dir().join(format!("stack-config-roundtrip-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+
+        let config = Config::new("shop".to_string(), Language::Rust);
+        config.write(&root).unwrap();
+        let read = Config::read(&root);
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(read.unwrap(), config);
+    }
+
+    fn read(yaml: &str) -> Result<Config> {
+        Config::read_from(&memory_fs(yaml))
+    }
+
+    #[test]
+    fn layout_folders_stay_inside_the_project() {
+        for language in ["Go", "Rust"] {
+            let config = read(&format!("version: 1\nname: shop\nlanguage: {}\nlayout:\n  service: src/services\n", language)).unwrap();
+            assert_eq!(config.folder(&Category::Service.into()), "src/services");
+
+            for folder in ["/tmp/services", "../services", "src/../../services", "''"] {
+                let yaml = format!("version: 1\nname: shop\nlanguage: {}\nlayout:\n  service: {}\n", language, folder);
+                assert!(matches!(read(&yaml), Err(Error::InvalidConfig(_))), "{}", folder);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_category_paths_stay_inside_the_project() {
+        let category = |folder: &str, template: &str| {
+            format!("version: 1\nname: shop\nlanguage: Go\ncategories:\n  job:\n    folder: {}\n    kind: struct\n    template: {}\n", folder, template)
+        };
+        assert!(read(&category("lib/jobs", ".stack/job.tmpl")).is_ok());
+
+        for (folder, template) in [("/tmp/jobs", "job.tmpl"), ("../jobs", "job.tmpl"), ("lib/jobs", "/etc/job.tmpl"), ("lib/jobs", "../job.tmpl")] {
+            assert!(matches!(read(&category(folder, template)), Err(Error::InvalidConfig(_))), "{} {}", folder, template);
+        }
+    }
+
+    fn memory_fs(yaml: &str) -> MemoryFs {
+        let fs = MemoryFs::new();
+        fs.write(Path::new(CONFIG_FILE), yaml).unwrap();
+        fs
+    }
+
+    #[tokio::test]
+    async fn migrate_upgrades_configs_without_a_version() {
+        let fs = memory_fs("name: shop\nlanguage: Go\n");
+        let (config, version) = Config::read_versioned(&fs).unwrap();
+        assert_eq!((config.version, version), (CONFIG_VERSION, 0));
+
+        Config::plan_migrate(&fs).unwrap().apply(&fs, &crate::toolchain::Toolchain::default()).await.unwrap();
+        assert_eq!(fs.read_to_string(Path::new(CONFIG_FILE)).unwrap(), config.to_yaml().unwrap());
+        assert!(Config::plan_migrate(&fs).unwrap().operations().is_empty());
+    }
+
+    #[test]
+    fn migrate_leaves_current_configs_alone() {
+        let fs = memory_fs(&format!("version: {}\nname: shop\nlanguage: Rust\n", CONFIG_VERSION));
+        assert_eq!(Config::read_versioned(&fs).unwrap().1, CONFIG_VERSION);
+        assert!(Config::plan_migrate(&fs).unwrap().operations().is_empty());
+    }
+
+    #[test]
+    fn migrate_rejects_unknown_versions() {
+        let future = memory_fs(&format!("version: {}\nname: shop\nlanguage: Go\n", CONFIG_VERSION + 1));
+        assert!(matches!(Config::plan_migrate(&future), Err(Error::InvalidConfigVersion(v)) if v == (CONFIG_VERSION + 1).to_string()));
+
+        for version in ["two", "1.5", "-1"] {
+            let fs = memory_fs(&format!("version: {}\nname: shop\nlanguage: Go\n", version));
+            assert!(matches!(Config::read_versioned(&fs), Err(Error::InvalidConfigVersion(_))), "{}", version);
+        }
+    }
+}
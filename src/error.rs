@@ -0,0 +1,123 @@
+use std::{fmt::Display, path::PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O operation on the project files failed
+    Io(std::io::Error),
+    /// No `stack.yaml` was found in the directory, nor in its parents when they were searched
+    ConfigNotFound { dir: PathBuf, searched_parents: bool },
+    /// `stack.yaml` exists but could not be parsed
+    ConfigParse(serde_yaml::Error),
+    /// `stack.yaml` has a version this stk does not know
+    InvalidConfigVersion(String),
+    /// `stack.yaml` is well-formed but its settings contradict each other
+    InvalidConfig(String),
+    /// A folder in the `layout` of `stack.yaml` can not be used for its category
+    InvalidLayout { category: String, folder: String },
+    /// The category is neither built-in nor declared in `stack.yaml`
+    UnknownCategory { name: String, expected: Vec<String> },
+    /// The config could not be serialized
+    ConfigSerialize(serde_yaml::Error),
+    /// `Cargo.toml` could not be parsed
+    ManifestParse(toml_edit::TomlError),
+    /// `Cargo.toml` is valid TOML but not shaped like a Cargo manifest
+    InvalidManifest(String),
+    /// The output of a command could not be serialized
+    OutputSerialize(serde_json::Error),
+    /// A `cargo` or `go` invocation exited unsuccessfully
+    ToolchainFailed { cmd: String, code: Option<i32>, stdout: String, stderr: String },
+    /// A `cargo` or `go` executable could not be started
+    ToolchainNotFound(String),
+    /// The file to generate already exists
+    FileExists(PathBuf),
+    /// The component to change does not exist
+    ComponentNotFound(PathBuf),
+    /// A project can not be initialized in a directory that already has files
+    DirectoryNotEmpty(PathBuf),
+    /// A project can not be initialized inside another stack project
+    ProjectExists(PathBuf),
+    /// A template configured in `stack.yaml` does not exist
+    TemplateNotFound(PathBuf),
+    /// A template refers to a variable that does not exist
+    UnknownTemplateVariable { template: String, variable: String },
+    /// The component or project name can not be used as an identifier
+    InvalidName(String),
+    /// A component path leaves its category folder or has empty segments
+    InvalidPath(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::ConfigNotFound { dir, searched_parents: true } => {
+                write!(f, "no stack.yaml found in {} or any parent directory", dir.display())
+            }
+            Error::ConfigNotFound { dir, searched_parents: false } => write!(f, "no stack.yaml found in {}", dir.display()),
+            Error::ConfigParse(e) => write!(f, "failed to parse config: {}", e),
+            Error::InvalidConfigVersion(version) => write!(
+                f,
+                "unsupported config version {}, this stk supports up to version {}",
+                version,
+                crate::config::CONFIG_VERSION
+            ),
+            Error::InvalidConfig(message) => write!(f, "invalid config: {}", message),
+            Error::UnknownCategory { name, expected } => {
+                write!(f, "unknown category '{}', expected one of: {}", name, expected.join(", "))
+            }
+            Error::InvalidLayout { category, folder } => {
+                write!(f, "invalid layout for {}: {} must be inside src/ to be part of the crate", category, folder)
+            }
+            Error::ConfigSerialize(e) => write!(f, "failed to serialize config: {}", e),
+            Error::ManifestParse(e) => write!(f, "failed to parse Cargo.toml: {}", e),
+            Error::InvalidManifest(message) => write!(f, "invalid Cargo.toml: {}", message),
+            Error::OutputSerialize(e) => write!(f, "failed to serialize output: {}", e),
+            Error::ToolchainFailed { cmd, code, stdout, stderr } => {
+                match code {
+                    Some(code) => write!(f, "`{}` exited with status {}", cmd, code)?,
+                    None => write!(f, "`{}` was terminated by a signal", cmd)?,
+                }
+                let output = if stderr.trim().is_empty() { stdout } else { stderr };
+                if !output.trim().is_empty() {
+                    write!(f, ":\n{}", output.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::ToolchainNotFound(program) => {
+                write!(f, "`{}` could not be found, is it installed and on the PATH? Set CARGO, GO or the toolchain section of stack.yaml to use another binary", program)
+            },
+            Error::FileExists(path) => write!(f, "{} already exists", path.display()),
+            Error::ComponentNotFound(path) => write!(f, "{} does not exist", path.display()),
+            Error::DirectoryNotEmpty(dir) => write!(f, "{} is not empty", dir.display()),
+            Error::ProjectExists(root) => write!(f, "{} is already a stack project", root.display()),
+            Error::TemplateNotFound(path) => write!(f, "template {} not found", path.display()),
+            Error::UnknownTemplateVariable { template, variable } => {
+                write!(f, "unknown variable '{}' in {}", variable, template)
+            }
+            Error::InvalidName(name) => write!(f, "invalid name '{}'", name),
+            Error::InvalidPath(path) => {
+                write!(f, "invalid path '{}': use a relative path without empty, '.' or '..' segments", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::ConfigParse(e) | Error::ConfigSerialize(e) => Some(e),
+            Error::ManifestParse(e) => Some(e),
+            Error::OutputSerialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
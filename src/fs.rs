@@ -0,0 +1,233 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The file system generators read from and write to.
+/// Paths are relative to the root of the project.
+pub trait Fs: Send + Sync {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Writes `contents` to `path`, replacing the file if it exists.
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    /// Appends `contents` to `path`, creating the file if it does not exist.
+    fn append(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes the directory at `path`, which must be empty.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn exists(&self, path: &Path) -> io::Result<bool>;
+
+    /// Lists the entries of the directory at `path`, sorted by name.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    /// The directory on disk paths are relative to, if any. Toolchain commands run there.
+    fn root(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// An entry of a directory listed with [`Fs::read_dir`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// The real file system, rooted at a project directory.
+#[derive(Debug, Clone)]
+pub struct DiskFs {
+    root: PathBuf,
+}
+
+impl DiskFs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+}
+
+impl Fs for DiskFs {
+    fn root(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(self.resolve(path))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        std::fs::write(self.resolve(path), contents)
+    }
+
+    fn append(&self, path: &Path, contents: &str) -> io::Result<()> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(self.resolve(path))?;
+        file.write_all(contents.as_bytes())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(self.resolve(path))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(self.resolve(path))
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_dir(self.resolve(path))
+    }
+
+    fn exists(&self, path: &Path) -> io::Result<bool> {
+        self.resolve(path).try_exists()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(self.resolve(path))? {
+            let entry = entry?;
+            entries.push(DirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir: entry.file_type()?.is_dir(),
+            });
+        }
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+/// A file system that only lives in memory.
+/// Useful to preview or test generators without touching the disk.
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: BTreeMap<PathBuf, String>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every file and its contents, sorted by path.
+    pub fn files(&self) -> BTreeMap<PathBuf, String> {
+        self.state.lock().unwrap().files.clone()
+    }
+
+    fn check_parent(state: &MemoryState, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !state.dirs.contains(parent) => {
+                Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", parent.display())))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Fs for MemoryFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let path = normalize(path);
+        let state = self.state.lock().unwrap();
+        state
+            .files
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.state.lock().unwrap();
+        Self::check_parent(&state, &path)?;
+        state.files.insert(path, contents.to_string());
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.state.lock().unwrap();
+        Self::check_parent(&state, &path)?;
+        state.files.entry(path).or_default().push_str(contents);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.state.lock().unwrap();
+        for dir in path.ancestors().filter(|d| !d.as_os_str().is_empty()) {
+            state.dirs.insert(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.state.lock().unwrap();
+        match state.files.remove(&path) {
+            Some(_) => Ok(()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display()))),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.state.lock().unwrap();
+        if !state.dirs.contains(&path) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+        }
+        let is_child = |p: &PathBuf| p.parent() == Some(path.as_path());
+        if state.dirs.iter().any(is_child) || state.files.keys().any(is_child) {
+            return Err(io::Error::new(io::ErrorKind::DirectoryNotEmpty, format!("{} is not empty", path.display())));
+        }
+        state.dirs.remove(&path);
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> io::Result<bool> {
+        let path = normalize(path);
+        let state = self.state.lock().unwrap();
+        Ok(state.files.contains_key(&path) || state.dirs.contains(&path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let path = normalize(path);
+        let state = self.state.lock().unwrap();
+        if !path.as_os_str().is_empty() && !state.dirs.contains(&path) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+        }
+
+        let children = |paths: Vec<&PathBuf>, is_dir: bool| -> Vec<DirEntry> {
+            paths
+                .into_iter()
+                .filter(|p| p.parent() == Some(path.as_path()))
+                .filter_map(|p| p.file_name())
+                .map(|name| DirEntry { name: name.to_string_lossy().into_owned(), is_dir })
+                .collect()
+        };
+        let mut entries = children(state.dirs.iter().collect(), true);
+        entries.extend(children(state.files.keys().collect(), false));
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+/// Drops `.` components so `./lib/service` and `lib/service` name the same entry.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().filter(|c| !matches!(c, Component::CurDir)).collect()
+}
@@ -1,14 +1,25 @@
-use std::error::Error;
-
-use tokio::{fs::File, io::AsyncWriteExt, process::Command};
-
-use crate::category::Category;
+use std::path::Path;
+
+use crate::{
+	category::{Category, CategoryRef, Kind},
+	config::Config,
+	fs::Fs,
+	plan::Plan,
+	inventory::files_under,
+	strfmt::{is_identifier, replace_identifier},
+	template::Variables,
+	toolchain::Toolchain,
+	Error, Result,
+};
 
 pub const PROVIDER_CODE : &str = "package provider
 
 import (
 	\"context\"
+	\"errors\"
+	\"io\"
 	\"reflect\"
+	\"runtime\"
 	\"strings\"
 	\"sync\"
 )
@@ -20,7 +31,7 @@ func (e ErrNotAFunction) Error() string {
 }
 
 func analyzeConstructor(constructFunction any) ([]reflect.Type, []reflect.Type, error) {
-	if reflect.TypeOf(constructFunction).Kind() != reflect.Func {
+	if t := reflect.TypeOf(constructFunction); t == nil || t.Kind() != reflect.Func {
 		return nil, nil, ErrNotAFunction{}
 	}
 
@@ -40,17 +51,32 @@ func analyzeConstructor(constructFunction any) ([]reflect.Type, []reflect.Type,
 	return args, returns, nil
 }
 
+// constructor is a registered constructor with the types it takes and returns.
+type constructor struct {
+	function reflect.Value
+	args     []reflect.Type
+	returns  []reflect.Type
+	built    bool
+}
+
+// component is a constructed value, kept in construction order for its lifecycle hooks.
+type component struct {
+	value   any
+	typ     reflect.Type
+	started bool
+}
+
 type Provider struct {
-	constructors map[reflect.Type]map[reflect.Value]struct{}
+	constructors []*constructor
+	components   []*component
 	container    map[reflect.Type]any
 	lock         sync.RWMutex
 }
 
 func New() *Provider {
 	return &Provider{
-		constructors: make(map[reflect.Type]map[reflect.Value]struct{}),
-		container:    make(map[reflect.Type]any),
-		lock:         sync.RWMutex{},
+		container: make(map[reflect.Type]any),
+		lock:      sync.RWMutex{},
 	}
 }
 
@@ -66,19 +92,41 @@ func (p *Provider) Register(constructFunction ...any) error {
 }
 
 func (p *Provider) register(constructFunction any) error {
-	args, _, err := analyzeConstructor(constructFunction)
+	args, returns, err := analyzeConstructor(constructFunction)
 	if err != nil {
 		return err
 	}
+	if !validConstructorReturns(returns) {
+		return ErrInvalidConstructorReturn{}
+	}
+
+	p.constructors = append(p.constructors, &constructor{
+		function: reflect.ValueOf(constructFunction),
+		args:     args,
+		returns:  returns,
+	})
+
+	return nil
+}
+
+// validConstructorReturns reports whether a constructor provides at least one value,
+// optionally followed by an error. Constructors may take no arguments at all.
+func validConstructorReturns(returns []reflect.Type) bool {
+	values := len(returns)
+	if values > 0 && returns[values-1] == getErrorType() {
+		values--
+	}
+	if values == 0 {
+		return false
+	}
 
-	for _, arg := range args {
-		if _, ok := p.constructors[arg]; !ok {
-			p.constructors[arg] = make(map[reflect.Value]struct{})
+	for _, ret := range returns[:values] {
+		if ret == getErrorType() {
+			return false
 		}
-		p.constructors[arg][reflect.ValueOf(constructFunction)] = struct{}{}
 	}
 
-	return nil
+	return true
 }
 
 func Get[T any](provider *Provider) (T, bool) {
@@ -197,220 +245,880 @@ func (e ErrInvalidConstructorReturn) Error() string {
 	return \"invalid constructor return\"
 }
 
-type ErrMaybeCyclicDependency struct {
-	cons []reflect.Value
+type ErrMissingDependency struct {
+	Type        reflect.Type
+	Constructor string
 }
 
-func (e ErrMaybeCyclicDependency) Error() string {
-	sb := strings.Builder{}
-	sb.WriteString(\"maybe cyclic dependency: \")
-	for i, con := range e.cons {
-		sb.WriteString(con.String())
-		if i != len(e.cons)-1 {
-			sb.WriteString(\", \")
-		}
+func (e ErrMissingDependency) Error() string {
+	return \"missing dependency: \" + e.Type.String() + \" needed by \" + e.Constructor
+}
+
+// ErrCyclicDependency lists the types whose constructors need each other, each needing the next.
+type ErrCyclicDependency struct {
+	Path []reflect.Type
+}
+
+func (e ErrCyclicDependency) Error() string {
+	names := make([]string, len(e.Path))
+	for i, t := range e.Path {
+		names[i] = t.String()
 	}
 
-	return sb.String()
+	return \"cyclic dependency: \" + strings.Join(names, \" -> \")
 }
 
 func getContextType() reflect.Type {
 	return reflect.TypeOf((*context.Context)(nil)).Elem()
 }
 
+func getErrorType() reflect.Type {
+	return reflect.TypeOf((*error)(nil)).Elem()
+}
+
+func funcName(function reflect.Value) string {
+	if f := runtime.FuncForPC(function.Pointer()); f != nil {
+		return f.Name()
+	}
+
+	return function.Type().String()
+}
+
+// providedType is the first value a constructor returns besides an error.
+func providedType(con *constructor) reflect.Type {
+	for _, out := range con.returns {
+		if out != getErrorType() {
+			return out
+		}
+	}
+
+	return con.function.Type()
+}
+
+// constructionOrder sorts the registered constructors so each one comes after the constructors of its arguments.
+// Constructors are visited in registration order, so the order and the errors are the same on every run.
+func (p *Provider) constructionOrder() ([]*constructor, error) {
+	producers := make(map[reflect.Type]*constructor)
+	for _, con := range p.constructors {
+		for _, out := range con.returns {
+			if _, ok := producers[out]; !ok && out != getErrorType() {
+				producers[out] = con
+			}
+		}
+	}
+
+	const (
+		visiting = iota + 1
+		visited
+	)
+	state := make(map[*constructor]int)
+	var order []*constructor
+	var stack []*constructor
+
+	var visit func(con *constructor) error
+	visit = func(con *constructor) error {
+		state[con] = visiting
+		stack = append(stack, con)
+
+		for _, arg := range con.args {
+			if _, ok := p.container[arg]; ok {
+				continue
+			}
+
+			dep, ok := producers[arg]
+			if !ok {
+				return ErrMissingDependency{Type: arg, Constructor: funcName(con.function)}
+			}
+
+			switch state[dep] {
+			case visited:
+				continue
+			case visiting:
+				var path []reflect.Type
+				for j := len(stack) - 1; j >= 0; j-- {
+					path = append([]reflect.Type{providedType(stack[j])}, path...)
+					if stack[j] == dep {
+						break
+					}
+				}
+				return ErrCyclicDependency{Path: append(path, providedType(dep))}
+			}
+
+			if err := visit(dep); err != nil {
+				return err
+			}
+		}
+
+		stack = stack[:len(stack)-1]
+		state[con] = visited
+		order = append(order, con)
+		return nil
+	}
+
+	for _, con := range p.constructors {
+		if state[con] == visited {
+			continue
+		}
+		if err := visit(con); err != nil {
+			return nil, err
+		}
+	}
+
+	return order, nil
+}
+
+// Construct calls every registered constructor in dependency order, each exactly once.
+// Missing or cyclic dependencies are reported before any constructor runs. If a constructor fails,
+// the ones built before it are kept and a later Construct continues with the rest.
 func (p *Provider) Construct(ctx context.Context) error {
 	p.lock.Lock()
 	defer p.lock.Unlock()
+	defer p.dropBuilt()
 
 	p.container[getContextType()] = ctx
-	count := 0
-	for len(p.constructors) > 0 {
-		for arg, constructors := range p.constructors {
-		ConsLoop:
-			for con := range constructors {
-				args := make([]reflect.Value, con.Type().NumIn())
-				for i := 0; i < con.Type().NumIn(); i++ {
-					at := con.Type().In(i)
-					v, ok := p.container[at]
-					if !ok {
-						continue ConsLoop
-					}
-					args[i] = reflect.ValueOf(v)
-				}
 
-				returns := con.Call(args)
+	order, err := p.constructionOrder()
+	if err != nil {
+		return err
+	}
 
-				for _, ret := range returns {
-					if ret.Type().Kind().String() == \"error\" {
-						if !ret.IsNil() {
-							return ret.Interface().(error)
-						}
-					}
+	for _, con := range order {
+		args := make([]reflect.Value, len(con.args))
+		for i, arg := range con.args {
+			args[i] = reflect.ValueOf(p.container[arg])
+		}
 
-					p.container[ret.Type()] = ret.Interface()
+		results := con.function.Call(args)
+		for _, ret := range results {
+			if ret.Type() == getErrorType() && !ret.IsNil() {
+				return ret.Interface().(error)
+			}
+		}
+
+		for _, ret := range results {
+			if ret.Type() != getErrorType() {
+				p.container[ret.Type()] = ret.Interface()
+				if !isNil(ret) {
+					p.components = append(p.components, &component{value: ret.Interface(), typ: ret.Type()})
 				}
+			}
+		}
+		con.built = true
+	}
 
-				count++
+	return nil
+}
 
-				delete(p.constructors[arg], con)
-			}
+// dropBuilt forgets the constructors that have run, keeping the others in registration order.
+func (p *Provider) dropBuilt() {
+	pending := p.constructors[:0]
+	for _, con := range p.constructors {
+		if !con.built {
+			pending = append(pending, con)
+		}
+	}
+	p.constructors = pending
+}
 
-			if len(p.constructors[arg]) == 0 {
-				delete(p.constructors, arg)
-			}
+func isNil(v reflect.Value) bool {
+	switch v.Kind() {
+	case reflect.Chan, reflect.Func, reflect.Interface, reflect.Map, reflect.Pointer, reflect.Slice:
+		return v.IsNil()
+	}
+
+	return false
+}
+
+// Starter is implemented by constructed values that need to be started, e.g. servers and workers.
+type Starter interface {
+	Start(ctx context.Context) error
+}
+
+// Stopper is implemented by constructed values that need to be stopped before the application exits.
+type Stopper interface {
+	Stop(ctx context.Context) error
+}
+
+// ErrHook is the error of the Start, Stop or Close method of a constructed value.
+type ErrHook struct {
+	Type reflect.Type
+	Hook string
+	Err  error
+}
+
+func (e ErrHook) Error() string {
+	return e.Type.String() + \".\" + e.Hook + \": \" + e.Err.Error()
+}
+
+func (e ErrHook) Unwrap() error {
+	return e.Err
+}
+
+// Start calls Start on the constructed values that implement Starter, in construction order,
+// so a value starts after the values it depends on. Values already started are skipped.
+// It returns at the first failure; Stop still stops the values started before it.
+func (p *Provider) Start(ctx context.Context) error {
+	p.lock.Lock()
+	defer p.lock.Unlock()
+
+	for _, c := range p.components {
+		starter, ok := c.value.(Starter)
+		if !ok || c.started {
+			continue
 		}
 
-		if count == 0 {
-			return ErrMaybeCyclicDependency{}
+		if err := callWithContext(ctx, func() error { return starter.Start(ctx) }); err != nil {
+			return ErrHook{Type: c.typ, Hook: \"Start\", Err: err}
 		}
+		c.started = true
 	}
 
 	return nil
 }
+
+// Stop stops the constructed values in reverse construction order, so a value stops before the
+// values it depends on. Values implementing Stopper are stopped unless they have a Start method
+// that was not called, and the others implementing io.Closer are closed.
+// Once ctx is done, the remaining values are left as they are and the context error is returned.
+// The errors of all hooks are joined.
+func (p *Provider) Stop(ctx context.Context) error {
+	p.lock.Lock()
+	defer p.lock.Unlock()
+
+	components := p.components
+	p.components = nil
+
+	var errs []error
+	for i := len(components) - 1; i >= 0; i-- {
+		c := components[i]
+
+		var hook string
+		var call func() error
+		switch value := c.value.(type) {
+		case Stopper:
+			if _, ok := value.(Starter); ok && !c.started {
+				continue
+			}
+			hook, call = \"Stop\", func() error { return value.Stop(ctx) }
+		case io.Closer:
+			hook, call = \"Close\", value.Close
+		default:
+			continue
+		}
+
+		if err := ctx.Err(); err != nil {
+			errs = append(errs, err)
+			break
+		}
+		if err := callWithContext(ctx, call); err != nil {
+			errs = append(errs, ErrHook{Type: c.typ, Hook: hook, Err: err})
+			if ctx.Err() != nil {
+				break
+			}
+		}
+	}
+
+	return errors.Join(errs...)
+}
+
+// callWithContext calls call, giving up with the context error once ctx is done.
+func callWithContext(ctx context.Context, call func() error) error {
+	done := make(chan error, 1)
+	go func() {
+		done <- call()
+	}()
+
+	select {
+	case err := <-done:
+		return err
+	case <-ctx.Done():
+		return ctx.Err()
+	}
+}
 ";
 
-pub async fn write_struct(writer: &mut File, name: &str) -> Result<(), Box<dyn Error>> {
-	writer.write(format!("package {}
+pub const STRUCT_TEMPLATE: &str = "package {{name}}
 	
-type {} struct {{
-}}
+type {{name}} struct {
+}
+
+func New() *{{name}} {
+	return &{{name}}{}
+}
 
-func New() *{} {{
-	return &{}{{}}
-}}
+";
 
-", name, name, name, name).as_bytes()).await?;
+pub const INTERFACE_TEMPLATE: &str = "package {{name}}
 
-	Ok(())
+type {{name}} interface {
 }
 
-pub async fn write_interface(writer: &mut File, name: &str) -> Result<(), Box<dyn Error>> {
-	writer.write(format!("package {}
+func Check(i {{name}}) {}
 
-type {} interface {{
-}}
+";
 
-func Check(i {}) {{}}
+pub const STATE_TEMPLATE: &str = "package {{name}}
 
-", name, name, name).as_bytes()).await?;
+// Run `go mod tidy` after generating to fetch gopkg.in/yaml.v3.
 
-	Ok(())
+import (
+	\"fmt\"
+	\"os\"
+	\"reflect\"
+	\"strconv\"
+
+	\"gopkg.in/yaml.v3\"
+)
+
+const envPrefix = \"{{name_upper}}_\"
+
+// Declare configuration fields with `env` and `yaml` tags, e.g.
+//	Port int `env:\"PORT\" yaml:\"port\"`
+type {{name}} struct {
+}
+
+func New() *{{name}} {
+	return &{{name}}{}
+}
+
+// FromFile loads the state from a YAML file.
+func FromFile(path string) (*{{name}}, error) {
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return nil, err
+	}
+
+	s := New()
+	if err := yaml.Unmarshal(data, s); err != nil {
+		return nil, err
+	}
+
+	return s, nil
+}
+
+// FromEnv loads the state from environment variables prefixed with {{name_upper}}_.
+func FromEnv() (*{{name}}, error) {
+	s := New()
+	if err := s.LoadEnv(); err != nil {
+		return nil, err
+	}
+
+	return s, nil
+}
+
+// LoadEnv overrides the fields tagged with `env` by the matching environment variables.
+func (s *{{name}}) LoadEnv() error {
+	v := reflect.ValueOf(s).Elem()
+	t := v.Type()
+	for i := 0; i < t.NumField(); i++ {
+		key, ok := t.Field(i).Tag.Lookup(\"env\")
+		if !ok {
+			continue
+		}
+
+		raw, ok := os.LookupEnv(envPrefix + key)
+		if !ok {
+			continue
+		}
+
+		f := v.Field(i)
+		switch f.Kind() {
+		case reflect.String:
+			f.SetString(raw)
+		case reflect.Bool:
+			b, err := strconv.ParseBool(raw)
+			if err != nil {
+				return fmt.Errorf(\"%s%s: %w\", envPrefix, key, err)
+			}
+			f.SetBool(b)
+		case reflect.Int, reflect.Int8, reflect.Int16, reflect.Int32, reflect.Int64:
+			n, err := strconv.ParseInt(raw, 10, f.Type().Bits())
+			if err != nil {
+				return fmt.Errorf(\"%s%s: %w\", envPrefix, key, err)
+			}
+			f.SetInt(n)
+		case reflect.Uint, reflect.Uint8, reflect.Uint16, reflect.Uint32, reflect.Uint64:
+			n, err := strconv.ParseUint(raw, 10, f.Type().Bits())
+			if err != nil {
+				return fmt.Errorf(\"%s%s: %w\", envPrefix, key, err)
+			}
+			f.SetUint(n)
+		case reflect.Float32, reflect.Float64:
+			n, err := strconv.ParseFloat(raw, f.Type().Bits())
+			if err != nil {
+				return fmt.Errorf(\"%s%s: %w\", envPrefix, key, err)
+			}
+			f.SetFloat(n)
+		default:
+			return fmt.Errorf(\"%s%s: unsupported field type %s\", envPrefix, key, f.Type())
+		}
+	}
+
+	return nil
 }
 
-pub async fn write_assembler(writer: &mut File, project_name: &str, name: &str) -> Result<(), Box<dyn Error>> {
-	writer.write(format!("package {}
+";
+
+pub const ENUM_TEMPLATE: &str = "package {{name}}
+
+type {{name}} int
+
+const (
+	Unknown {{name}} = iota
+)
+
+";
+
+pub const ASSEMBLER_TEMPLATE: &str = "package main
 
 import (
-	\"{}/gen/provider\"
+	\"context\"
+	\"log\"
+	\"os\"
 	\"os/signal\"
 	\"syscall\"
-	\"context\"
+	\"time\"
+
+	\"{{project}}/gen/provider\"
 )
 
-func main() {{
+// shutdownTimeout bounds how long the components may take to stop.
+const shutdownTimeout = 10 * time.Second
+
+func main() {
 	ctx, cancel := signal.NotifyContext(context.Background(), syscall.SIGINT, syscall.SIGTERM)
 	defer cancel()
-	done := ctx.Done()
 
 	p := provider.New()
 
 	// Register your dependencies here
-	p.Register()
+	if err := p.Register(); err != nil {
+		log.Fatal(err)
+	}
+
+	err := run(ctx, p)
+	if err != nil {
+		log.Print(err)
+	}
+
+	// Stop or close the instances in reverse order, also those constructed before a failure
+	stopCtx, stopCancel := context.WithTimeout(context.Background(), shutdownTimeout)
+	if err := p.Stop(stopCtx); err != nil {
+		log.Print(err)
+	}
+	stopCancel()
+
+	if err != nil {
+		os.Exit(1)
+	}
+}
 
+// run constructs and starts the instances, then waits for a signal to shut down.
+func run(ctx context.Context, p *provider.Provider) error {
 	// Create instances of your dependencies here
-	p.Construct(ctx)
+	if err := p.Construct(ctx); err != nil {
+		return err
+	}
 
 	// Update instances of your dependencies here
-	for _, f := range []any{{}} {{
+	for _, f := range []any{} {
 		provider.Update(p, f)
-	}}
+	}
+
+	// Start the instances with a Start(ctx) error method, in dependency order
+	if err := p.Start(ctx); err != nil {
+		return err
+	}
 
 	// Run your application here
-	for _, f := range []any{{}} {{
+	for _, f := range []any{} {
 		provider.JustRun(p, f)
-	}}
-
-	<-done
-}}
-
-	", name, project_name).as_bytes()).await?;
+	}
 
-	Ok(())
+	<-ctx.Done()
+	return nil
 }
+";
 
-const SERVICE_FOLDER : &str = "./lib/service";
-const MODEL_FOLDER : &str = "./lib/model";
-const CONTRACT_FOLDER : &str = "./lib/contract";
-const MEDIATOR_FOLDER : &str = "./internal/mediator";
-const AGGREGATOR_FOLDER : &str = "./internal/aggregator";
-const HANDLER_FOLDER : &str = "./internal/handler";
-const ADAPTER_FOLDER : &str = "./internal/adapter";
-const SERVER_FOLDER : &str = "./internal/server";
-const MESSAGE_FOLDER : &str = "./lib/message";
-const PROTOCOL_FOLDER : &str = "./lib/protocol";
-const STATE_FOLDER : &str = "./lib/state";
-const ASSEMBLER_FOLDER : &str = "./cmd";
+/// The template used for a category when the project does not override it.
+pub fn builtin_template(category: &Category) -> &'static str {
+	match category {
+		Category::Contract => INTERFACE_TEMPLATE,
+		Category::State => STATE_TEMPLATE,
+		Category::Assembler => ASSEMBLER_TEMPLATE,
+		_ => STRUCT_TEMPLATE,
+	}
+}
 
-pub async fn init_go_mod(name: &str) -> Result<(), Box<dyn Error>> {
-	let output = Command::new("go")
-		.arg("mod")
-		.arg("init")
-		.arg(name)
-		.output().await?;
+/// The template of custom categories of `kind` that have no template of their own.
+pub fn kind_template(kind: Kind) -> &'static str {
+	match kind {
+		Kind::Struct => STRUCT_TEMPLATE,
+		Kind::Trait => INTERFACE_TEMPLATE,
+		Kind::Enum => ENUM_TEMPLATE,
+		Kind::Binary => ASSEMBLER_TEMPLATE,
+	}
+}
 
-	if !output.status.success() {
-		return Err("Failed to initialize go mod".into());
+/// The folder of a category in a Go project when `stack.yaml` has no layout for it.
+pub fn default_folder(category: &Category) -> &'static str {
+	match category {
+		Category::Service => "./lib/service",
+		Category::Model => "./lib/model",
+		Category::Contract => "./lib/contract",
+		Category::Mediator => "./internal/mediator",
+		Category::Aggregator => "./internal/aggregator",
+		Category::Handler => "./internal/handler",
+		Category::Adapter => "./internal/adapter",
+		Category::Server => "./internal/server",
+		Category::Message => "./lib/message",
+		Category::Protocol => "./lib/protocol",
+		Category::State => "./lib/state",
+		Category::Assembler => "./cmd",
 	}
+}
+
+pub fn plan_init(config: &Config) -> Plan {
+	let mut plan = Plan::new();
+	plan.run("go", &["mod", "init", &config.name]);
 
 	let provider_path = "gen/provider";
 
-	tokio::fs::create_dir_all(provider_path).await?;
-	tokio::fs::create_dir_all(SERVICE_FOLDER).await?;
-	tokio::fs::create_dir_all(MODEL_FOLDER).await?;
-	tokio::fs::create_dir_all(CONTRACT_FOLDER).await?;
-	tokio::fs::create_dir_all(MEDIATOR_FOLDER).await?;
-	tokio::fs::create_dir_all(AGGREGATOR_FOLDER).await?;
-	tokio::fs::create_dir_all(HANDLER_FOLDER).await?;
-	tokio::fs::create_dir_all(ADAPTER_FOLDER).await?;
-	tokio::fs::create_dir_all(SERVER_FOLDER).await?;
-	tokio::fs::create_dir_all(MESSAGE_FOLDER).await?;
-	tokio::fs::create_dir_all(PROTOCOL_FOLDER).await?;
-	// tokio::fs::create_dir_all(STATE_FOLDER).await?;
-	tokio::fs::create_dir_all(ASSEMBLER_FOLDER).await?;
-	
-	let mut provider_file = File::create(format!("{}/provider.go", provider_path)).await?;
+	plan.create_dir(provider_path);
+	for category in config.categories() {
+		plan.create_dir(config.folder(&category));
+	}
 
-	provider_file.write(PROVIDER_CODE.as_bytes()).await?;
+	plan.create(format!("{}/provider.go", provider_path), PROVIDER_CODE);
 
-	Ok(())
+	plan
+}
+
+pub async fn init_go_mod(fs: &dyn Fs, config: &Config, toolchain: &Toolchain) -> Result<()> {
+	plan_init(config).apply(fs, toolchain).await
 }
 
-pub async fn generate_file(name: &str, path: &str, project_name: &str, category: &Category) -> Result<(), Box<dyn Error>> {
+pub fn plan_generate(fs: &dyn Fs, config: &Config, name: &str, path: &str, category: &CategoryRef, force: bool) -> Result<Plan> {
+	if !is_identifier(name) {
+		return Err(Error::InvalidName(name.to_string()));
+	}
+
 	let lower_case_name = name.to_lowercase();
-	let file = match category {
-		Category::Service => format!("{}/{}/{}", SERVICE_FOLDER, path, lower_case_name),
-		Category::Model => format!("{}/{}/{}", MODEL_FOLDER, path, lower_case_name),
-		Category::Contract => format!("{}/{}/{}", CONTRACT_FOLDER, path, lower_case_name),
-		Category::Mediator => format!("{}/{}/{}", MEDIATOR_FOLDER, path, lower_case_name),
-		Category::Aggregator => format!("{}/{}/{}", AGGREGATOR_FOLDER, path, lower_case_name),
-		Category::Handler => format!("{}/{}/{}", HANDLER_FOLDER, path, lower_case_name),
-		Category::Adapter => format!("{}/{}/{}", ADAPTER_FOLDER, path, lower_case_name),
-		Category::Server => format!("{}/{}/{}", SERVER_FOLDER, path, lower_case_name),
-		Category::Message => format!("{}/{}/{}", MESSAGE_FOLDER, path, lower_case_name),
-		Category::Protocol => format!("{}/{}/{}", PROTOCOL_FOLDER, path, lower_case_name),
-		Category::State => format!("{}/{}/{}", STATE_FOLDER, path, lower_case_name),
-		Category::Assembler => format!("{}/{}/{}", ASSEMBLER_FOLDER, path, lower_case_name),
+	let prefix = config.folder(category);
+
+	let dir = match path.trim_matches('/') {
+		"" => format!("{}/{}", prefix, lower_case_name),
+		path => format!("{}/{}/{}", prefix, path, lower_case_name),
 	};
+	let file = format!("{}/{}.go", dir, lower_case_name);
 
-	tokio::fs::create_dir_all(&file).await?;
+	if !force && fs.exists(Path::new(&file))? {
+		return Err(Error::FileExists(file.into()));
+	}
 
-	let mut file = File::create(format!("{}/{}.go", file, lower_case_name)).await?;
+	let mut plan = Plan::new();
+	plan.create_dir(&dir);
 
-	match category {
-		Category::Contract => write_interface(&mut file, name).await?,
-		Category::Assembler => write_assembler(&mut file, project_name, name).await?,
-		_ => write_struct(&mut file, name).await?,
+	let module_path = format!("{}/{}", config.name, dir.trim_start_matches("./"));
+	let vars = Variables::component(config, category, path, name, &lower_case_name, &module_path);
+	let code = config.template(fs, category)?.render(&vars)?;
+	plan.create(file, code);
+
+	Ok(plan)
+}
+
+/// Plans the removal of a component: its file, and the directories left empty by it up to the category folder.
+pub fn plan_remove(fs: &dyn Fs, config: &Config, name: &str, path: &str, category: &CategoryRef) -> Result<Plan> {
+	let lower_case_name = name.to_lowercase();
+	let prefix = config.folder(category);
+
+	let dir = match path.trim_matches('/') {
+		"" => format!("{}/{}", prefix, lower_case_name),
+		path => format!("{}/{}/{}", prefix, path, lower_case_name),
 	};
+	let file_name = format!("{}.go", lower_case_name);
+	let file = format!("{}/{}", dir, file_name);
+
+	if !fs.exists(Path::new(&file))? {
+		return Err(Error::ComponentNotFound(file.into()));
+	}
+
+	let mut plan = Plan::new();
+	plan.remove_file(&file);
+
+	prune_dirs(&mut plan, fs, &prefix, &dir, &[file_name], None)?;
+
+	Ok(plan)
+}
+
+/// Removes the directories left empty once `removed` are gone from `dir`, up to `prefix`.
+/// Directories leading to `keep` are kept, since a component is about to be created there.
+fn prune_dirs(plan: &mut Plan, fs: &dyn Fs, prefix: &str, dir: &str, removed: &[String], keep: Option<&str>) -> Result<()> {
+	let mut current = dir.to_string();
+	let mut removed = removed.to_vec();
+	while current != prefix {
+		let kept = keep.is_some_and(|keep| keep == current || keep.starts_with(&format!("{}/", current)));
+		let entries = fs.read_dir(Path::new(&current))?;
+		if kept || entries.iter().any(|e| !removed.contains(&e.name)) {
+			break;
+		}
+		plan.remove_dir(&current);
+
+		let (parent, name) = current.rsplit_once('/').expect("component directories are inside their category folder");
+		removed = vec![name.to_string()];
+		current = parent.to_string();
+	}
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+/// Plans moving a component to `new_path` and renaming it to `new_name` in its category folder.
+/// The package and type are renamed in the component's files, and import paths and qualified
+/// references are rewritten in every Go file of the project importing the component.
+pub fn plan_relocate(
+	fs: &dyn Fs,
+	config: &Config,
+	category: &CategoryRef,
+	(path, name): (&str, &str),
+	(new_path, new_name): (&str, &str),
+) -> Result<Plan> {
+	if !is_identifier(new_name) {
+		return Err(Error::InvalidName(new_name.to_string()));
+	}
+
+	let prefix = config.folder(category);
+	let (old_folder, new_folder) = (name.to_lowercase(), new_name.to_lowercase());
+	let component_dir = |path: &str, folder: &str| match path.trim_matches('/') {
+		"" => format!("{}/{}", prefix, folder),
+		path => format!("{}/{}/{}", prefix, path, folder),
+	};
+	let (old_dir, new_dir) = (component_dir(path, &old_folder), component_dir(new_path, &new_folder));
+	let old_file = format!("{}/{}.go", old_dir, old_folder);
+
+	if !fs.exists(Path::new(&old_file))? {
+		return Err(Error::ComponentNotFound(old_file.into()));
+	}
+	if old_dir != new_dir && fs.exists(Path::new(&new_dir))? {
+		return Err(Error::FileExists(new_dir.into()));
+	}
+
+	// The package follows the name as the component declares it: `package Order` from the
+	// built-in templates, `package order` after the folder, or a package of its own that is kept.
+	let old_package = package_clause(&fs.read_to_string(Path::new(&old_file))?).unwrap_or_else(|| old_folder.clone());
+	let new_package = match old_package.as_str() {
+		package if package == name => new_name.to_string(),
+		package if package == old_folder => new_folder.clone(),
+		package => package.to_string(),
+	};
+
+	let rename = {
+		let (old_package, new_package) = (old_package.clone(), new_package.clone());
+		let (from, to) = (name.to_string(), new_name.to_string());
+		move |data: &str| {
+			let data = data
+				.split('\n')
+				.map(|line| match line.trim() == format!("package {}", old_package) {
+					true => format!("package {}", new_package),
+					false => line.to_string(),
+				})
+				.collect::<Vec<_>>()
+				.join("\n");
+			replace_identifier(&data, &from, &to)
+		}
+	};
+
+	let mut plan = Plan::new();
+	let files = fs.read_dir(Path::new(&old_dir))?.into_iter().filter(|e| !e.is_dir).map(|e| e.name).collect::<Vec<_>>();
+
+	if old_dir == new_dir {
+		for file in &files {
+			let rename = rename.clone();
+			plan.modify(format!("{}/{}", old_dir, file), &format!("rename {} to {}", name, new_name), move |data| rename(data));
+		}
+	} else {
+		plan.create_dir(&new_dir);
+		for file in &files {
+			let target = match *file == format!("{}.go", old_folder) {
+				true => format!("{}.go", new_folder),
+				false => file.clone(),
+			};
+			let data = fs.read_to_string(Path::new(&format!("{}/{}", old_dir, file)))?;
+			plan.create(format!("{}/{}", new_dir, target), rename(&data));
+			plan.remove_file(format!("{}/{}", old_dir, file));
+		}
+		prune_dirs(&mut plan, fs, &prefix, &old_dir, &files, Some(&new_dir))?;
+	}
+
+	let import_path = |dir: &str| format!("\"{}/{}\"", config.name, dir.trim_start_matches("./"));
+	let rewrite = {
+		let (old_import, new_import) = (import_path(&old_dir), import_path(&new_dir));
+		let (old_qualifier, new_qualifier) = (format!("{}.", old_package), format!("{}.", new_package));
+		let (from, to) = (format!("{}{}", new_qualifier, name), format!("{}{}", new_qualifier, new_name));
+		std::sync::Arc::new(move |data: &str| {
+			if !data.contains(&old_import) {
+				return data.to_string();
+			}
+			let data = data.replace(&old_import, &new_import);
+			let data = replace_identifier(&data, &old_qualifier, &new_qualifier);
+			replace_identifier(&data, &from, &to)
+		})
+	};
+
+	let component_files = old_dir.trim_start_matches("./");
+	for file in files_under(fs, "")? {
+		let in_component = file.rsplit_once('/').is_some_and(|(dir, _)| dir == component_files);
+		if in_component || !file.ends_with(".go") {
+			continue;
+		}
+
+		let data = fs.read_to_string(Path::new(&file))?;
+		if rewrite(&data) != data {
+			let rewrite = rewrite.clone();
+			plan.modify(file, &format!("update references to {}", name), move |data| rewrite(data));
+		}
+	}
+
+	Ok(plan)
+}
+
+/// The package name declared by the `package` clause of a Go file.
+fn package_clause(code: &str) -> Option<String> {
+	code.lines().find_map(|line| {
+		let package = line.trim().strip_prefix("package ")?;
+		let package = package.split("//").next()?.trim();
+		is_identifier(package).then(|| package.to_string())
+	})
+}
+
+pub async fn generate_file(fs: &dyn Fs, config: &Config, name: &str, path: &str, category: &CategoryRef, force: bool) -> Result<()> {
+	plan_generate(fs, config, name, path, category, force)?.apply(fs, &Toolchain::default()).await
+}
+
+/// Identifies the component defined by `file`, at `relative` inside its category folder.
+/// Components live in `<path>/<name>/<name>.go`; the name is taken from the type declared for it.
+pub fn find_component(fs: &dyn Fs, file: &str, relative: &str) -> Result<Option<(String, String)>> {
+	let segments = relative.split('/').collect::<Vec<_>>();
+	let (stem, package) = match segments.as_slice() {
+		[.., package, file_name] => match file_name.strip_suffix(".go") {
+			Some(stem) if stem == *package => (stem, package),
+			_ => return Ok(None),
+		},
+		_ => return Ok(None),
+	};
+	let path = segments[..segments.len() - 2].join("/");
+
+	let code = fs.read_to_string(Path::new(file))?;
+	let declared = code.lines().find_map(|line| {
+		let name = line.strip_prefix("type ")?.split_whitespace().next()?;
+		(name.to_lowercase() == *package).then(|| name.to_string())
+	});
+
+	Ok(Some((path, declared.unwrap_or_else(|| stem.to_string()))))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::category::Category;
+	use crate::config::Language;
+	use crate::fs::MemoryFs;
+
+	fn config() -> Config {
+		Config::new("example.com/shop".to_string(), Language::Go)
+	}
+
+	async fn apply(fs: &MemoryFs, plan: Result<Plan>) {
+		plan.unwrap().apply(fs, &Toolchain::default()).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn generate_writes_the_component_package() {
+		let (fs, config) = (MemoryFs::new(), config());
+		let service = CategoryRef::from(Category::Service);
+		apply(&fs, plan_generate(&fs, &config, "Order", "billing", &service, false)).await;
+
+		let files = fs.files();
+		assert_eq!(files.keys().collect::<Vec<_>>(), [Path::new("lib/service/billing/order/order.go")]);
+		assert!(files[Path::new("lib/service/billing/order/order.go")].starts_with("package Order\n"));
+
+		let again = plan_generate(&fs, &config, "Order", "billing", &service, false);
+		assert!(matches!(again, Err(Error::FileExists(_))));
+		apply(&fs, plan_generate(&fs, &config, "Order", "billing", &service, true)).await;
+	}
+
+	#[tokio::test]
+	async fn assembler_stops_the_provider_when_construction_fails() {
+		let (fs, config) = (MemoryFs::new(), config());
+		apply(&fs, plan_generate(&fs, &config, "Api", "", &Category::Assembler.into(), false)).await;
+
+		let code = fs.read_to_string(Path::new("cmd/api/api.go")).unwrap();
+		assert!(code.contains("import (\n\t\"context\""));
+		assert!(code.contains("\t\"example.com/shop/gen/provider\"\n"));
+		assert_eq!(code.matches("log.Fatal").count(), 1, "only a failed Register may exit before Stop");
+		assert!(code.contains("if err := p.Construct(ctx); err != nil {\n\t\treturn err\n\t}"));
+
+		let stop = code.find("p.Stop(stopCtx)").unwrap();
+		assert!(code.find("err := run(ctx, p)").unwrap() < stop && stop < code.find("os.Exit(1)").unwrap());
+	}
+
+	#[tokio::test]
+	async fn remove_prunes_directories_left_empty() {
+		let (fs, config) = (MemoryFs::new(), config());
+		let service = CategoryRef::from(Category::Service);
+		apply(&fs, plan_generate(&fs, &config, "Order", "billing", &service, false)).await;
+		apply(&fs, plan_generate(&fs, &config, "Ledger", "", &service, false)).await;
+		apply(&fs, plan_remove(&fs, &config, "Order", "billing", &service)).await;
+
+		assert!(!fs.exists(Path::new("lib/service/billing")).unwrap());
+		assert!(fs.exists(Path::new("lib/service/ledger/ledger.go")).unwrap());
+
+		let missing = plan_remove(&fs, &config, "Order", "billing", &service);
+		assert!(matches!(missing, Err(Error::ComponentNotFound(_))));
+	}
+
+	#[tokio::test]
+	async fn move_rewrites_import_paths() {
+		let (fs, config) = (MemoryFs::new(), config());
+		let service = CategoryRef::from(Category::Service);
+		apply(&fs, plan_generate(&fs, &config, "Order", "billing", &service, false)).await;
+		let handler = "internal/handler/checkout/checkout.go";
+		fs.create_dir_all(Path::new("internal/handler/checkout")).unwrap();
+		fs.write(Path::new(handler), "package checkout\n\nimport \"example.com/shop/lib/service/billing/order\"\n\nvar _ = Order.New()\n").unwrap();
+
+		apply(&fs, plan_relocate(&fs, &config, &service, ("billing", "Order"), ("sales", "Order"))).await;
+
+		assert!(!fs.exists(Path::new("lib/service/billing")).unwrap());
+		assert!(fs.read_to_string(Path::new("lib/service/sales/order/order.go")).unwrap().starts_with("package Order\n"));
+		assert_eq!(
+			fs.read_to_string(Path::new(handler)).unwrap(),
+			"package checkout\n\nimport \"example.com/shop/lib/service/sales/order\"\n\nvar _ = Order.New()\n"
+		);
+	}
+
+	#[tokio::test]
+	async fn rename_rewrites_package_qualifiers_of_builtin_template() {
+		let (fs, config) = (MemoryFs::new(), config());
+		let service = CategoryRef::from(Category::Service);
+		apply(&fs, plan_generate(&fs, &config, "Order", "billing", &service, false)).await;
+
+		let handler = "internal/handler/checkout/checkout.go";
+		fs.create_dir_all(Path::new("internal/handler/checkout")).unwrap();
+		fs.write(
+			Path::new(handler),
+			"package checkout\n\nimport \"example.com/shop/lib/service/billing/order\"\n\nfunc Handle() *Order.Order {\n\treturn Order.New()\n}\n",
+		)
+		.unwrap();
+
+		apply(&fs, plan_relocate(&fs, &config, &service, ("billing", "Order"), ("billing", "Purchase"))).await;
+
+		let files = fs.files();
+		let component = &files[Path::new("lib/service/billing/purchase/purchase.go")];
+		assert!(component.starts_with("package Purchase\n"));
+		assert!(component.contains("type Purchase struct"));
+		assert!(!files.contains_key(Path::new("lib/service/billing/order/order.go")));
+		assert_eq!(
+			files[Path::new(handler)],
+			"package checkout\n\nimport \"example.com/shop/lib/service/billing/purchase\"\n\nfunc Handle() *Purchase.Purchase {\n\treturn Purchase.New()\n}\n"
+		);
+	}
+}
@@ -0,0 +1,137 @@
+use std::{fmt::Write, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    category::CategoryRef,
+    config::{Config, Language},
+    fs::Fs,
+    go, rust, Error, Result,
+};
+
+/// A component found in the project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Component {
+    pub category: String,
+    /// The path of the component inside its category folder, `/`-separated
+    pub path: String,
+    /// The name of the type the component defines
+    pub name: String,
+    /// The file the component lives in, relative to the project root
+    pub file: String,
+}
+
+/// Scans the category folders for components, or only the folder of `category`.
+/// Components are ordered by category, then by file.
+pub fn list(fs: &dyn Fs, config: &Config, category: Option<&CategoryRef>) -> Result<Vec<Component>> {
+    let categories = match category {
+        Some(category) => vec![category.clone()],
+        None => config.categories(),
+    };
+    let folders = config.categories().iter().map(|c| clean(&config.folder(c))).collect::<Vec<_>>();
+
+    let mut components = Vec::new();
+    for category in &categories {
+        let folder = clean(&config.folder(category));
+        // Folders of other categories nested in this one hold their own components.
+        let nested = folders.iter().filter(|f| **f != folder).map(String::as_str).collect::<Vec<_>>();
+
+        let mut files = Vec::new();
+        walk(fs, &folder, "", &nested, &mut files)?;
+
+        for relative in files {
+            let file = format!("{}/{}", folder, relative);
+            // Categories sharing a folder would otherwise report the same file twice.
+            if components.iter().any(|c: &Component| c.file == file) {
+                continue;
+            }
+            let found = match config.language {
+                Language::Go => go::find_component(fs, &file, &relative)?,
+                Language::Rust => rust::find_component(fs, &file, &relative)?,
+            };
+            if let Some((path, name)) = found {
+                components.push(Component { category: category.to_string(), path, name, file });
+            }
+        }
+    }
+
+    Ok(components)
+}
+
+/// Renders components as an aligned table with a header row.
+pub fn render_table(components: &[Component]) -> String {
+    let header = ["CATEGORY", "PATH", "NAME", "FILE"];
+    let rows = components
+        .iter()
+        .map(|c| [c.category.as_str(), c.path.as_str(), c.name.as_str(), c.file.as_str()])
+        .collect::<Vec<_>>();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(header).chain(rows) {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths) {
+            let _ = write!(line, "{:<width$}  ", cell, width = width);
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    out
+}
+
+pub fn render_json(components: &[Component]) -> Result<String> {
+    serde_json::to_string_pretty(components).map_err(Error::OutputSerialize)
+}
+
+/// Every file below `dir`, relative to the project root. An empty `dir` is the project root.
+/// Hidden directories are skipped.
+pub fn files_under(fs: &dyn Fs, dir: &str) -> Result<Vec<String>> {
+    let root = clean(dir);
+    let mut files = Vec::new();
+    walk(fs, &root, "", &[], &mut files)?;
+
+    Ok(match root.as_str() {
+        "" => files,
+        root => files.into_iter().map(|f| format!("{}/{}", root, f)).collect(),
+    })
+}
+
+/// Collects the files below `root/dir`, relative to `root`, skipping the `nested` folders.
+fn walk(fs: &dyn Fs, root: &str, dir: &str, nested: &[&str], files: &mut Vec<String>) -> Result<()> {
+    let full = match (root, dir) {
+        (root, "") => root.to_string(),
+        ("", dir) => dir.to_string(),
+        (root, dir) => format!("{}/{}", root, dir),
+    };
+
+    let entries = match fs.read_dir(Path::new(&full)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let relative = match dir {
+            "" => entry.name.clone(),
+            dir => format!("{}/{}", dir, entry.name),
+        };
+        if !entry.is_dir {
+            files.push(relative);
+        } else if !entry.name.starts_with('.') && !nested.contains(&format!("{}/{}", root, relative).as_str()) {
+            walk(fs, root, &relative, nested, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn clean(folder: &str) -> String {
+    folder.trim_start_matches("./").trim_end_matches('/').to_string()
+}
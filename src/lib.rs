@@ -1,6 +1,17 @@
 pub mod cli;
 pub mod config;
+pub mod error;
+pub mod fs;
 pub mod go;
+pub mod inventory;
+pub mod manifest;
+pub mod modfile;
+pub mod plan;
+pub mod project;
 pub mod rust;
 pub mod category;
-pub mod strfmt;
\ No newline at end of file
+pub mod strfmt;
+pub mod template;
+pub mod toolchain;
+
+pub use error::{Error, Result};
@@ -0,0 +1,168 @@
+//! Editing `Cargo.toml` through a TOML document, so formatting and comments are kept.
+
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, Value};
+
+use crate::{Error, Result};
+
+pub const MANIFEST_FILE: &str = "Cargo.toml";
+
+/// Sets `[package].name`.
+pub fn set_package_name(source: &str, name: &str) -> Result<String> {
+    let mut doc = parse(source)?;
+    set(&mut doc["package"]["name"], name);
+    Ok(doc.to_string())
+}
+
+/// Sets `[dependencies].<name>`, as a plain version or with `features`.
+pub fn add_dependency(source: &str, name: &str, version: &str, features: &[String]) -> Result<String> {
+    let mut doc = parse(source)?;
+    let dependencies = doc
+        .entry("dependencies")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| Error::InvalidManifest("`dependencies` must be a table".to_string()))?;
+
+    let spec = match features.is_empty() {
+        true => value(version),
+        false => {
+            let mut spec = InlineTable::new();
+            spec.insert("version", version.into());
+            spec.insert("features", features.iter().collect::<Array>().into());
+            value(spec)
+        }
+    };
+    dependencies.insert(name, spec);
+
+    Ok(doc.to_string())
+}
+
+/// Declares a `[[bin]]` target. An existing target with the same name is pointed at `path` instead,
+/// and duplicates of it are dropped.
+pub fn add_bin(source: &str, name: &str, path: &str) -> Result<String> {
+    let mut doc = parse(source)?;
+    let bins = bins(&mut doc)?;
+    let named = |bin: &Table| bin.get("name").and_then(Item::as_str) == Some(name);
+
+    let first = bins.iter().position(named);
+    match first {
+        Some(first) => {
+            set(&mut bins.get_mut(first).expect("position is in bounds")["path"], path);
+            let mut seen = false;
+            bins.retain(|bin| !named(bin) || !std::mem::replace(&mut seen, true));
+        }
+        None => {
+            let mut bin = Table::new();
+            bin["name"] = value(name);
+            bin["path"] = value(path);
+            bins.push(bin);
+        }
+    }
+
+    Ok(doc.to_string())
+}
+
+/// Removes the `[[bin]]` targets built from `path`.
+pub fn remove_bin(source: &str, path: &str) -> Result<String> {
+    let mut doc = parse(source)?;
+    if doc.get("bin").is_none() {
+        return Ok(source.to_string());
+    }
+
+    let bins = bins(&mut doc)?;
+    bins.retain(|bin| bin.get("path").and_then(Item::as_str) != Some(path));
+    if bins.is_empty() {
+        doc.remove("bin");
+    }
+
+    Ok(doc.to_string())
+}
+
+/// Sets `item` to `new`, keeping the comments and whitespace around the old value.
+fn set(item: &mut Item, new: impl Into<Value>) {
+    let mut new = new.into();
+    if let Some(old) = item.as_value() {
+        *new.decor_mut() = old.decor().clone();
+    }
+    *item = Item::Value(new);
+}
+
+fn parse(source: &str) -> Result<DocumentMut> {
+    source.parse::<DocumentMut>().map_err(Error::ManifestParse)
+}
+
+fn bins(doc: &mut DocumentMut) -> Result<&mut ArrayOfTables> {
+    doc.entry("bin")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| Error::InvalidManifest("`bin` must be an array of tables".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"# The shop backend.
+[package]
+name = "old" # renamed on init
+version = "0.1.0"
+
+[[bin]]
+name = "server"
+path = "src/bin/old.rs" # moved
+
+# Keep the worker.
+[[bin]]
+name = "worker"
+path = "src/bin/worker.rs"
+
+[[bin]]
+name = "server"
+path = "src/bin/server.rs"
+
+[dependencies]
+name = "1.0" # a crate called `name`
+"#;
+
+    #[test]
+    fn add_bin_collapses_duplicates() {
+        let edited = add_bin(MANIFEST, "server", "src/bin/server.rs").unwrap();
+        assert_eq!(edited.matches("name = \"server\"").count(), 1);
+        assert!(edited.contains("[[bin]]\nname = \"server\"\npath = \"src/bin/server.rs\" # moved\n"));
+        assert!(edited.contains("# Keep the worker.\n[[bin]]\nname = \"worker\""));
+        assert!(!edited.contains("src/bin/old.rs"));
+        assert_eq!(add_bin(&edited, "server", "src/bin/server.rs").unwrap(), edited);
+    }
+
+    #[test]
+    fn add_bin_appends_new_targets() {
+        let edited = add_bin("[package]\nname = \"shop\"\n", "server", "src/bin/server.rs").unwrap();
+        assert_eq!(edited, "[package]\nname = \"shop\"\n\n[[bin]]\nname = \"server\"\npath = \"src/bin/server.rs\"\n");
+    }
+
+    #[test]
+    fn remove_bin_keeps_other_targets() {
+        let edited = remove_bin(MANIFEST, "src/bin/server.rs").unwrap();
+        assert!(!edited.contains("src/bin/server.rs"));
+        assert!(edited.contains("path = \"src/bin/old.rs\""));
+        assert!(edited.contains("# Keep the worker.\n[[bin]]\nname = \"worker\""));
+
+        let edited = remove_bin("[package]\nname = \"shop\"\n\n[[bin]]\nname = \"server\"\npath = \"src/bin/server.rs\"\n", "src/bin/server.rs").unwrap();
+        assert_eq!(edited, "[package]\nname = \"shop\"\n");
+    }
+
+    #[test]
+    fn set_package_name_only_touches_the_package() {
+        let edited = set_package_name(MANIFEST, "shop").unwrap();
+        assert!(edited.starts_with("# The shop backend.\n[package]\nname = \"shop\""));
+        assert!(edited.contains("[[bin]]\nname = \"server\""));
+        assert!(edited.contains("name = \"1.0\" # a crate called `name`\n"));
+        assert_eq!(edited, MANIFEST.replace("name = \"old\"", "name = \"shop\""));
+    }
+
+    #[test]
+    fn add_dependency_keeps_comments() {
+        let edited = add_dependency(MANIFEST, "tokio", "1.40.0", &["full".to_string()]).unwrap();
+        assert!(edited.starts_with("# The shop backend.\n"));
+        assert!(edited.ends_with("name = \"1.0\" # a crate called `name`\ntokio = { version = \"1.40.0\", features = [\"full\"] }\n"));
+    }
+}
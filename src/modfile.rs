@@ -0,0 +1,194 @@
+//! Editing the `mod` declarations of a Rust module file (`lib.rs` or `mod.rs`).
+//!
+//! Only top-level `mod name;` declarations are recognized, with any visibility. Attributes and
+//! comments directly above a declaration belong to it and move or disappear with it.
+
+use crate::{strfmt::is_identifier, Error, Result};
+
+/// A `mod` declaration, spanning `start..end` in the lines of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Declaration {
+    name: String,
+    start: usize,
+    end: usize,
+}
+
+/// Declares `pub mod name;` unless `source` already declares it.
+/// The declaration is inserted before the first one that sorts after it, or after the last one.
+/// Names that are not identifiers are refused, since the declaration could not be read back.
+pub fn add_declaration(source: &str, name: &str) -> Result<String> {
+    if !is_identifier(name) {
+        return Err(Error::InvalidName(name.to_string()));
+    }
+
+    let mut lines = source.lines().collect::<Vec<_>>();
+    let found = declarations(&lines);
+    if found.iter().any(|d| d.name == name) {
+        return Ok(source.to_string());
+    }
+
+    let at = match found.iter().find(|d| d.name.as_str() > name) {
+        Some(next) => next.start,
+        None => found.last().map_or(lines.len(), |last| last.end),
+    };
+    let declaration = format!("pub mod {};", name);
+    lines.insert(at, &declaration);
+
+    Ok(join(&lines))
+}
+
+/// Removes every declaration of the module `name` along with its attributes and comments.
+pub fn remove_declaration(source: &str, name: &str) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let removed = declarations(&lines).into_iter().filter(|d| d.name == name).collect::<Vec<_>>();
+    if removed.is_empty() {
+        return source.to_string();
+    }
+
+    let kept = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !removed.iter().any(|d| (d.start..d.end).contains(i)))
+        .map(|(_, line)| *line)
+        .collect::<Vec<_>>();
+
+    join(&kept)
+}
+
+fn declarations(lines: &[&str]) -> Vec<Declaration> {
+    let mut found = Vec::new();
+    // The first line of the attributes and comments above the current line.
+    let mut attached: Option<usize> = None;
+    let mut attribute_depth = 0i32;
+    let mut brace_depth = 0i32;
+    let mut in_block_comment = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        if in_block_comment {
+            in_block_comment = !trimmed.contains("*/");
+            continue;
+        }
+        if attribute_depth > 0 {
+            attribute_depth += bracket_balance(trimmed);
+            continue;
+        }
+        if brace_depth > 0 {
+            brace_depth += brace_balance(trimmed);
+            continue;
+        }
+
+        if trimmed.starts_with("//") && !trimmed.starts_with("//!") {
+            attached.get_or_insert(i);
+        } else if trimmed.starts_with("/*") && !trimmed.starts_with("/*!") {
+            attached.get_or_insert(i);
+            in_block_comment = !trimmed.contains("*/");
+        } else if trimmed.starts_with("#[") {
+            attached.get_or_insert(i);
+            attribute_depth = bracket_balance(trimmed);
+        } else if let Some(name) = parse_declaration(trimmed) {
+            found.push(Declaration { name, start: attached.take().unwrap_or(i), end: i + 1 });
+        } else {
+            attached = None;
+            brace_depth = brace_balance(trimmed);
+        }
+    }
+
+    found
+}
+
+/// Parses `[pub[(…)]] mod name;`, ignoring a trailing comment.
+fn parse_declaration(line: &str) -> Option<String> {
+    let code = line.split("//").next()?.trim();
+    let rest = match code.strip_prefix("pub") {
+        Some(rest) if rest.starts_with('(') => rest[rest.find(')')? + 1..].trim_start(),
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+        Some(_) => return None,
+        None => code,
+    };
+
+    let name = rest.strip_prefix("mod")?;
+    if !name.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let name = name.trim().strip_suffix(';')?.trim_end();
+    let name = name.strip_prefix("r#").unwrap_or(name);
+
+    is_identifier(name).then(|| name.to_string())
+}
+
+fn bracket_balance(line: &str) -> i32 {
+    line.matches('[').count() as i32 - line.matches(']').count() as i32
+}
+
+fn brace_balance(line: &str) -> i32 {
+    line.matches('{').count() as i32 - line.matches('}').count() as i32
+}
+
+fn join(lines: &[&str]) -> String {
+    match lines.is_empty() {
+        true => String::new(),
+        false => format!("{}\n", lines.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_keeps_declarations_sorted() {
+        let source = "pub mod user_profile;\n";
+        assert_eq!(add_declaration(source, "user").unwrap(), "pub mod user;\npub mod user_profile;\n");
+        assert_eq!(add_declaration(source, "zone").unwrap(), "pub mod user_profile;\npub mod zone;\n");
+        assert_eq!(add_declaration("", "user").unwrap(), "pub mod user;\n");
+    }
+
+    #[test]
+    fn add_is_idempotent() {
+        let source = "//! Services.\n\npub mod billing;\npub(crate) mod user; // internal\nmod r#type;\n";
+        for name in ["billing", "user", "type"] {
+            assert_eq!(add_declaration(source, name).unwrap(), source);
+        }
+
+        let added = add_declaration(source, "order").unwrap();
+        assert_eq!(add_declaration(&added, "order").unwrap(), added);
+    }
+
+    #[test]
+    fn add_refuses_names_that_are_not_identifiers() {
+        assert!(matches!(add_declaration("pub mod billing;\n", "my-dir"), Err(Error::InvalidName(_))));
+        assert!(matches!(add_declaration("", ".."), Err(Error::InvalidName(_))));
+    }
+
+    #[test]
+    fn add_skips_attributes_and_comments_of_declarations() {
+        let source = "pub mod billing;\n// Only with the server feature.\n#[cfg(feature = \"server\")]\npub mod server;\n";
+        assert_eq!(
+            add_declaration(source, "order").unwrap(),
+            "pub mod billing;\npub mod order;\n// Only with the server feature.\n#[cfg(feature = \"server\")]\npub mod server;\n"
+        );
+        assert_eq!(add_declaration(source, "server").unwrap(), source);
+    }
+
+    #[test]
+    fn add_ignores_inline_modules() {
+        let source = "pub mod billing;\n\n#[cfg(test)]\nmod tests {\n    mod user;\n}\n";
+        assert_eq!(add_declaration(source, "user").unwrap(), "pub mod billing;\npub mod user;\n\n#[cfg(test)]\nmod tests {\n    mod user;\n}\n");
+    }
+
+    #[test]
+    fn remove_takes_attached_attributes_and_comments() {
+        let source = "pub mod billing;\n\n/// The server.\n#[cfg(all(\n    feature = \"server\",\n    unix\n))]\npub mod server;\n#[cfg(not(unix))]\npub mod server;\npub mod user;\n";
+        assert_eq!(remove_declaration(source, "server"), "pub mod billing;\n\npub mod user;\n");
+    }
+
+    #[test]
+    fn remove_only_matches_the_whole_name() {
+        let source = "pub mod user;\npub mod user_profile;\n";
+        assert_eq!(remove_declaration(source, "user"), "pub mod user_profile;\n");
+        assert_eq!(remove_declaration(source, "profile"), source);
+        assert_eq!(remove_declaration("pub mod user;\n", "user"), "");
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use similar::TextDiff;
+
+use crate::{fs::Fs, toolchain::Toolchain, Result};
+
+pub type Edit = Box<dyn Fn(&str) -> Result<String> + Send + Sync>;
+
+/// A single step a generator wants to perform on the project.
+pub enum Operation {
+    /// Runs a toolchain command
+    Run { program: String, args: Vec<String> },
+    /// Creates a directory and all of its parents
+    CreateDir(PathBuf),
+    /// Creates a file, replacing its contents if it already exists
+    Create { path: PathBuf, contents: String },
+    /// Appends to a file, creating it if it does not exist
+    Append { path: PathBuf, contents: String },
+    /// Rewrites a file with `edit`. A missing file is edited as if it were empty
+    Modify { path: PathBuf, description: String, edit: Edit },
+    /// Deletes a file
+    RemoveFile(PathBuf),
+    /// Deletes a directory, which must be empty by then
+    RemoveDir(PathBuf),
+}
+
+/// The ordered operations of a command that changes the project.
+/// A plan can be applied, or rendered to show what applying it would do.
+#[derive(Default)]
+pub struct Plan {
+    operations: Vec<Operation>,
+}
+
+impl Plan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn run(&mut self, program: &str, args: &[&str]) {
+        self.operations.push(Operation::Run {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        });
+    }
+
+    pub fn create_dir(&mut self, path: impl Into<PathBuf>) {
+        self.operations.push(Operation::CreateDir(path.into()));
+    }
+
+    pub fn create(&mut self, path: impl Into<PathBuf>, contents: impl Into<String>) {
+        self.operations.push(Operation::Create { path: path.into(), contents: contents.into() });
+    }
+
+    pub fn append(&mut self, path: impl Into<PathBuf>, contents: impl Into<String>) {
+        self.operations.push(Operation::Append { path: path.into(), contents: contents.into() });
+    }
+
+    pub fn modify(&mut self, path: impl Into<PathBuf>, description: &str, edit: impl Fn(&str) -> String + Send + Sync + 'static) {
+        self.try_modify(path, description, move |data| Ok(edit(data)));
+    }
+
+    /// Like [`Plan::modify`], for edits that fail on contents they can not understand.
+    pub fn try_modify(
+        &mut self,
+        path: impl Into<PathBuf>,
+        description: &str,
+        edit: impl Fn(&str) -> Result<String> + Send + Sync + 'static,
+    ) {
+        self.operations.push(Operation::Modify {
+            path: path.into(),
+            description: description.to_string(),
+            edit: Box::new(edit),
+        });
+    }
+
+    pub fn remove_file(&mut self, path: impl Into<PathBuf>) {
+        self.operations.push(Operation::RemoveFile(path.into()));
+    }
+
+    pub fn remove_dir(&mut self, path: impl Into<PathBuf>) {
+        self.operations.push(Operation::RemoveDir(path.into()));
+    }
+
+    pub fn extend(&mut self, other: Plan) {
+        self.operations.extend(other.operations);
+    }
+
+    /// Performs every operation in order. Toolchain commands run in the root of `fs`,
+    /// unless `toolchain` has a directory of its own.
+    pub async fn apply(&self, fs: &dyn Fs, toolchain: &Toolchain) -> Result<()> {
+        let toolchain = match (&toolchain.dir, fs.root()) {
+            (None, Some(root)) => toolchain.clone().in_dir(root),
+            _ => toolchain.clone(),
+        };
+
+        for operation in &self.operations {
+            match operation {
+                Operation::Run { program, args } => {
+                    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+                    toolchain.run(program, &args).await?;
+                }
+                Operation::CreateDir(path) => fs.create_dir_all(path)?,
+                Operation::Create { path, contents } => fs.write(path, contents)?,
+                Operation::Append { path, contents } => fs.append(path, contents)?,
+                Operation::Modify { path, edit, .. } => {
+                    let data = read(fs, path)?.unwrap_or_default();
+                    fs.write(path, &edit(&data)?)?;
+                }
+                Operation::RemoveFile(path) => fs.remove_file(path)?,
+                Operation::RemoveDir(path) => fs.remove_dir(path)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describes every operation without touching the project.
+    /// With `diff`, file changes are followed by a unified diff against the current contents.
+    pub fn render(&self, fs: &dyn Fs, diff: bool) -> Result<String> {
+        let mut out = String::new();
+        // Contents the plan has produced so far, so later operations diff against earlier ones.
+        // Removed files map to `None`.
+        let mut files: HashMap<PathBuf, Option<String>> = HashMap::new();
+        let mut ran_toolchain = false;
+
+        for operation in &self.operations {
+            match operation {
+                Operation::Run { program, args } => {
+                    ran_toolchain = true;
+                    let _ = writeln!(out, "run     {} {}", program, args.join(" "));
+                }
+                Operation::CreateDir(path) => {
+                    let _ = writeln!(out, "mkdir   {}", path.display());
+                }
+                Operation::Create { path, contents } => {
+                    let old = current(fs, &files, path)?;
+                    let verb = if old.is_some() { "replace" } else { "create " };
+                    let _ = writeln!(out, "{} {}", verb, path.display());
+                    if diff {
+                        out.push_str(&unified_diff(path, old.as_deref(), Some(contents)));
+                    }
+                    files.insert(path.clone(), Some(contents.clone()));
+                }
+                Operation::Append { path, contents } => {
+                    let old = current(fs, &files, path)?;
+                    let new = format!("{}{}", old.as_deref().unwrap_or_default(), contents);
+                    let _ = writeln!(out, "append  {}", path.display());
+                    if diff {
+                        out.push_str(&unified_diff(path, old.as_deref(), Some(&new)));
+                    }
+                    files.insert(path.clone(), Some(new));
+                }
+                Operation::Modify { path, description, edit } => {
+                    let old = current(fs, &files, path)?;
+                    let _ = writeln!(out, "modify  {} ({})", path.display(), description);
+                    // The file may be produced by a toolchain command that has not run yet.
+                    if old.is_none() && ran_toolchain {
+                        continue;
+                    }
+                    let new = edit(old.as_deref().unwrap_or_default())?;
+                    if diff {
+                        out.push_str(&unified_diff(path, old.as_deref(), Some(&new)));
+                    }
+                    files.insert(path.clone(), Some(new));
+                }
+                Operation::RemoveFile(path) => {
+                    let old = current(fs, &files, path)?;
+                    let _ = writeln!(out, "remove  {}", path.display());
+                    if diff {
+                        out.push_str(&unified_diff(path, old.as_deref(), None));
+                    }
+                    files.insert(path.clone(), None);
+                }
+                Operation::RemoveDir(path) => {
+                    let _ = writeln!(out, "rmdir   {}", path.display());
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+fn read(fs: &dyn Fs, path: &Path) -> Result<Option<String>> {
+    match fs.read_to_string(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn current(fs: &dyn Fs, files: &HashMap<PathBuf, Option<String>>, path: &Path) -> Result<Option<String>> {
+    match files.get(path) {
+        Some(data) => Ok(data.clone()),
+        None => read(fs, path),
+    }
+}
+
+fn unified_diff(path: &Path, old: Option<&str>, new: Option<&str>) -> String {
+    let path = path.display().to_string();
+    let old_header = match old {
+        Some(_) => format!("a/{}", path),
+        None => "/dev/null".to_string(),
+    };
+    let new_header = match new {
+        Some(_) => format!("b/{}", path),
+        None => "/dev/null".to_string(),
+    };
+
+    TextDiff::from_lines(old.unwrap_or_default(), new.unwrap_or_default())
+        .unified_diff()
+        .header(&old_header, &new_header)
+        .to_string()
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::find_root, Error, Result};
+
+/// Entries that may already exist in a directory a project is initialized into.
+const IGNORED_ENTRIES: &[&str] = &[".git"];
+
+/// The directory name for a project. Go module paths like `github.com/org/app` use their last segment.
+pub fn dir_name(name: &str) -> &str {
+    name.trim_end_matches('/').rsplit('/').next().unwrap_or(name)
+}
+
+/// Checks that `dir` can hold a new project: it must be empty (or missing) and not inside another stack project.
+pub fn check_init_target(dir: &Path) -> Result<()> {
+    if let Ok(root) = find_root(dir) {
+        return Err(Error::ProjectExists(root));
+    }
+
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    if !existing_entries(dir)?.is_empty() {
+        return Err(Error::DirectoryNotEmpty(dir.to_path_buf()));
+    }
+
+    Ok(())
+}
+
+/// Remembers the state of a directory before a project is initialized into it,
+/// so a failed initialization can be undone.
+#[derive(Debug)]
+pub struct Rollback {
+    dir: PathBuf,
+    created_dir: bool,
+    existing: HashSet<OsString>,
+}
+
+impl Rollback {
+    /// Creates `dir` if needed and records what it contains.
+    pub fn prepare(dir: &Path) -> Result<Self> {
+        let created_dir = !dir.exists();
+        if created_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut existing = HashSet::new();
+        for entry in std::fs::read_dir(dir)? {
+            existing.insert(entry?.file_name());
+        }
+
+        Ok(Self { dir: dir.to_path_buf(), created_dir, existing })
+    }
+
+    /// Removes everything created since [`Rollback::prepare`].
+    pub fn undo(self) -> Result<()> {
+        if self.created_dir {
+            std::fs::remove_dir_all(&self.dir)?;
+            return Ok(());
+        }
+
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if self.existing.contains(&entry.file_name()) {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(entry.path())?;
+            } else {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn existing_entries(dir: &Path) -> Result<Vec<OsString>> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if !IGNORED_ENTRIES.iter().any(|ignored| name == *ignored) {
+            entries.push(name);
+        }
+    }
+
+    Ok(entries)
+}
@@ -1,237 +1,783 @@
-use std::error::Error;
+use std::path::Path;
+
+use crate::{
+    category::{Category, CategoryRef, Kind},
+    config::Config,
+    fs::Fs,
+    plan::Plan,
+    inventory::files_under,
+    manifest::{self, MANIFEST_FILE},
+    modfile,
+    strfmt::{contains_identifier, is_identifier, pascal_to_snake, replace_identifier, snake_to_pascal},
+    template::Variables,
+    toolchain::Toolchain,
+    Error, Result,
+};
+
+/// The module assemblers wire components with, written to `src/provider.rs`.
+pub const PROVIDER_CODE: &str = include_str!("rust/provider.rs");
+
+// Compiled here as well, so the generated module is type-checked and tested with this crate.
+#[cfg(test)]
+mod provider;
+
+pub const STRUCT_TEMPLATE: &str = "pub struct {{name}} {
+}
+
+impl {{name}} {
+	pub fn new() -> Self {
+		{{name}} {}
+	}
+}
+";
 
-use tokio::{fs::File, io::AsyncWriteExt, process::Command};
+pub const ENUM_TEMPLATE: &str = "pub enum {{name}} {
+}
 
-use crate::{category::Category, strfmt::pascal_to_snake};
+";
 
+pub const TRAIT_TEMPLATE: &str = "pub trait {{name}} {
+}
+";
 
-pub async fn write_struct(writer: &mut File, name: &str) -> Result<(), std::io::Error> {
-    writer.write(format!("pub struct {} {{\n}}\n\n", name).as_bytes()).await?;
-    writer.write(format!("impl {} {{\n\t", name).as_bytes()).await?;
-    writer.write(format!("pub fn new() -> Self {{\n\t\t{} {{}}\n\t}}\n", name).as_bytes()).await?;
-    writer.write("}\n".as_bytes()).await?;
+pub const STATE_TEMPLATE: &str = "use serde::Deserialize;
 
-    Ok(())
+const ENV_PREFIX: &str = \"{{name_upper}}_\";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct {{name}} {
 }
 
-pub async fn write_enum(writer: &mut File, name: &str) -> Result<(), std::io::Error> {
-    writer.write(format!("pub enum {} {{\n}}\n\n", name).as_bytes()).await?;
+impl {{name}} {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Loads the state from a YAML file.
+	pub fn from_yaml(path: impl AsRef<std::path::Path>) -> Result<Self, Box<dyn std::error::Error>> {
+		let file = std::fs::File::open(path)?;
+		Ok(serde_yaml::from_reader(file)?)
+	}
+
+	/// Loads the state from environment variables prefixed with `{{name_upper}}_`.
+	/// `{{name_upper}}_PORT=8080` fills the field `port`.
+	pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+		let mut values = serde_yaml::Mapping::new();
+		for (key, value) in std::env::vars() {
+			if let Some(field) = key.strip_prefix(ENV_PREFIX) {
+				let value = serde_yaml::from_str(&value).unwrap_or(serde_yaml::Value::String(value));
+				values.insert(serde_yaml::Value::String(field.to_lowercase()), value);
+			}
+		}
+		Ok(serde_yaml::from_value(serde_yaml::Value::Mapping(values))?)
+	}
+}
+";
 
-    Ok(())
+pub const ASSEMBLER_TEMPLATE: &str = "use {{crate_name}}::provider::{BoxError, Provider};
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+	let mut provider = Provider::new();
+
+	// Register your dependencies here
+	// provider.register(|(): ()| async { Ok(Service::new()) });
+
+	// Create instances of your dependencies here
+	provider.construct().await?;
+
+	// Run your application here
+
+	Ok(())
 }
+";
 
-pub async fn write_trait(writer: &mut File, name: &str) -> Result<(), std::io::Error> {
-    writer.write(format!("pub trait {} {{\n}}\n", name).as_bytes()).await?;
+/// The template used for a category when the project does not override it.
+pub fn builtin_template(category: &Category) -> &'static str {
+    match category {
+        Category::Service => STRUCT_TEMPLATE,
+        Category::Model => STRUCT_TEMPLATE,
+        Category::Contract => TRAIT_TEMPLATE,
+        Category::Mediator => STRUCT_TEMPLATE,
+        Category::Aggregator => STRUCT_TEMPLATE,
+        Category::Handler => STRUCT_TEMPLATE,
+        Category::Adapter => STRUCT_TEMPLATE,
+        Category::Server => STRUCT_TEMPLATE,
+        Category::Message => ENUM_TEMPLATE,
+        Category::Protocol => STRUCT_TEMPLATE,
+        Category::State => STATE_TEMPLATE,
+        Category::Assembler => ASSEMBLER_TEMPLATE,
+    }
+}
 
-    Ok(())
+/// The template of custom categories of `kind` that have no template of their own.
+pub fn kind_template(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Struct => STRUCT_TEMPLATE,
+        Kind::Trait => TRAIT_TEMPLATE,
+        Kind::Enum => ENUM_TEMPLATE,
+        Kind::Binary => ASSEMBLER_TEMPLATE,
+    }
 }
 
-pub async fn write_assembler(writer: &mut File) -> Result<(), std::io::Error> {
-    writer.write(format!("#[tokio::main]\nasync fn main() -> Result<(), Box<dyn std::error::Error>> {{\n\tOk(())\n}}").as_bytes()).await?;
+/// The folder of a category in a Rust project when `stack.yaml` has no layout for it.
+pub fn default_folder(category: &Category) -> &'static str {
+    match category {
+        Category::Service => "src/component/service",
+        Category::Model => "src/component/model",
+        Category::Contract => "src/component/contract",
+        Category::Mediator => "src/controller/mediator",
+        Category::Aggregator => "src/controller/aggregator",
+        Category::Handler => "src/controller/handler",
+        Category::Adapter => "src/controller/adapter",
+        Category::Server => "src/controller/server",
+        Category::Message => "src/component/message",
+        Category::Protocol => "src/component/protocol",
+        Category::State => "src/component/state",
+        Category::Assembler => "src/bin",
+    }
+}
 
-    Ok(())
+const SRC_FOLDER: &str = "src";
+const PROVIDER_MODULE: &str = "provider";
+
+/// The modules leading from `src` to `folder`, which must live under `src`.
+fn module_segments<'a>(category: &CategoryRef, folder: &'a str) -> Result<Vec<&'a str>> {
+    match folder.strip_prefix(SRC_FOLDER) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let segments = rest.split('/').filter(|d| !d.is_empty()).collect::<Vec<_>>();
+            match segments.iter().all(|d| is_identifier(d)) {
+                true => Ok(segments),
+                false => Err(Error::InvalidLayout { category: category.to_string(), folder: folder.to_string() }),
+            }
+        }
+        _ => Err(Error::InvalidLayout { category: category.to_string(), folder: folder.to_string() }),
+    }
+}
+
+/// Checks that every segment of a component path can be used as a module name.
+fn check_path(path: &str) -> Result<()> {
+    match path.split('/').filter(|d| !d.is_empty()).find(|d| !is_identifier(d)) {
+        Some(segment) => Err(Error::InvalidName(segment.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// The file declaring the modules of `dir`: `src/lib.rs` for the crate root, `mod.rs` otherwise.
+fn module_file(dir: &str) -> String {
+    if dir == SRC_FOLDER {
+        format!("{}/lib.rs", SRC_FOLDER)
+    } else {
+        format!("{}/mod.rs", dir)
+    }
+}
+
+/// The dependencies of new projects with their minimum versions, written without registry access.
+/// Cargo reads them as caret requirements, so `1.40.0` accepts any compatible `1.x` release.
+pub const DEFAULT_DEPENDENCIES: &[(&str, &str, &[&str])] = &[
+    ("tokio", "1.40.0", &["full"]),
+    ("serde", "1.0.210", &["derive"]),
+    ("serde_yaml", "0.9.34", &[]),
+];
+
+/// A crate a new project depends on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+    pub features: Vec<String>,
 }
 
-const SERVICE_FOLDER: &str = "src/component/service";
-const SERVICE_FILE: &str = "src/component/service/mod.rs";
-const MODEL_FOLDER: &str = "src/component/model";
-const MODEL_FILE: &str = "src/component/model/mod.rs";
-const CONTRACT_FOLDER: &str = "src/component/contract";
-const CONTRACT_FILE: &str = "src/component/contract/mod.rs";
-const MEDIATOR_FOLDER: &str = "src/controller/mediator";
-const MEDIATOR_FILE: &str = "src/controller/mediator/mod.rs";
-const AGGREGATOR_FOLDER: &str = "src/controller/aggregator";
-const AGGREGATOR_FILE: &str = "src/controller/aggregator/mod.rs";
-const HANDLER_FOLDER: &str = "src/controller/handler";
-const HANDLER_FILE: &str = "src/controller/handler/mod.rs";
-const ADAPTER_FOLDER: &str = "src/controller/adapter";
-const ADAPTER_FILE: &str = "src/controller/adapter/mod.rs";
-const SERVER_FOLDER: &str = "src/controller/server";
-const SERVER_FILE: &str = "src/controller/server/mod.rs";
-const MESSAGE_FOLDER: &str = "src/component/message";
-const MESSAGE_FILE: &str = "src/component/message/mod.rs";
-const PROTOCOL_FOLDER: &str = "src/component/protocol";
-const PROTOCOL_FILE: &str = "src/component/protocol/mod.rs";
-const STATE_FOLDER: &str = "src/component/state";
-const STATE_FILE: &str = "src/component/state/mod.rs";
-const ASSEMBLER_FOLDER: &str = "src/bin";
-
-pub async fn init_cargo(name: &str) -> Result<(), std::io::Error> {
-    let output = Command::new("cargo")
-        .arg("init")
-        .arg(".")
-        .arg("--lib")
-        .output().await?;
-
-    if !output.status.success() {
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Failed to init cargo"));
-    }
-
-    let cargo_data = tokio::fs::read_to_string("Cargo.toml").await?;
-    let lines = cargo_data.split('\n').collect::<Vec<&str>>();
-    let mut new_cargo_data = String::new();
-    for line in lines {
-        if line.starts_with("name") {
-            new_cargo_data.push_str(&format!("name = \"{}\"\n", name));
+/// How a Rust project is initialized.
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    pub dependencies: Vec<Dependency>,
+    /// Add the dependencies with `cargo add`, which needs registry access, instead of writing them to Cargo.toml
+    pub cargo_add: bool,
+}
+
+impl Default for InitOptions {
+    fn default() -> Self {
+        let dependencies = DEFAULT_DEPENDENCIES
+            .iter()
+            .map(|(name, version, features)| Dependency {
+                name: name.to_string(),
+                version: version.to_string(),
+                features: features.iter().map(|f| f.to_string()).collect(),
+            })
+            .collect();
+
+        Self { dependencies, cargo_add: false }
+    }
+}
+
+impl InitOptions {
+    /// Sets the version requirement of `name`, keeping the features of a default dependency.
+    pub fn set_version(&mut self, name: &str, version: &str) {
+        match self.dependencies.iter_mut().find(|d| d.name == name) {
+            Some(dependency) => dependency.version = version.to_string(),
+            None => self.dependencies.push(Dependency {
+                name: name.to_string(),
+                version: version.to_string(),
+                features: Vec::new(),
+            }),
+        }
+    }
+}
+
+pub fn plan_init(config: &Config, options: &InitOptions) -> Result<Plan> {
+    let mut plan = Plan::new();
+    plan.run("cargo", &["init", ".", "--lib"]);
+
+    let name = config.name.clone();
+    plan.try_modify(MANIFEST_FILE, "set the package name", move |cargo_data| manifest::set_package_name(cargo_data, &name));
+
+    for dependency in &options.dependencies {
+        if options.cargo_add {
+            let spec = format!("{}@{}", dependency.name, dependency.version);
+            let features = dependency.features.join(",");
+            let mut args = vec!["add", spec.as_str()];
+            if !features.is_empty() {
+                args.extend(["-F", features.as_str()]);
+            }
+            plan.run("cargo", &args);
         } else {
-            new_cargo_data.push_str(&format!("{}\n", line));
-        }
-    }
-
-    tokio::fs::write("Cargo.toml", new_cargo_data).await?;
-
-    let output = Command::new("cargo")
-        .arg("add")
-        .arg("tokio")
-        .arg("-F")
-        .arg("full")
-        .output().await?;
-
-    if !output.status.success() {
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Failed to add tokio"));
-    }
-
-    let output = Command::new("cargo")
-        .arg("add")
-        .arg("serde")
-        .arg("-F")
-        .arg("derive")
-        .output().await?;
-
-    if !output.status.success() {
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Failed to add serde"));
-    }
-
-    tokio::fs::create_dir_all(SERVICE_FOLDER).await?;
-    File::create(SERVICE_FILE).await?;
-    tokio::fs::create_dir_all(MODEL_FOLDER).await?;
-    File::create(MODEL_FILE).await?;
-    tokio::fs::create_dir_all(CONTRACT_FOLDER).await?;
-    File::create(CONTRACT_FILE).await?;
-    tokio::fs::create_dir_all(MEDIATOR_FOLDER).await?;
-    File::create(MEDIATOR_FILE).await?;
-    tokio::fs::create_dir_all(AGGREGATOR_FOLDER).await?;
-    File::create(AGGREGATOR_FILE).await?;
-    tokio::fs::create_dir_all(HANDLER_FOLDER).await?;
-    File::create(HANDLER_FILE).await?;
-    tokio::fs::create_dir_all(ADAPTER_FOLDER).await?;
-    File::create(ADAPTER_FILE).await?;
-    tokio::fs::create_dir_all(SERVER_FOLDER).await?;
-    File::create(SERVER_FILE).await?;
-    tokio::fs::create_dir_all(MESSAGE_FOLDER).await?;
-    File::create(MESSAGE_FILE).await?;
-    tokio::fs::create_dir_all(PROTOCOL_FOLDER).await?;
-    File::create(PROTOCOL_FILE).await?;
-    tokio::fs::create_dir_all(STATE_FOLDER).await?;
-    File::create(STATE_FILE).await?;
-
-    let mut mod_file = File::create("src/component/mod.rs").await?;
-    mod_file.write("pub mod service;\npub mod model;\npub mod contract;\npub mod message;\npub mod state;\npub mod protocol;\n".as_bytes()).await?;
-    let mut mod_file = File::create("src/controller/mod.rs").await?;
-    mod_file.write("pub mod aggregator;\npub mod handler;\npub mod adapter;\npub mod server;\npub mod mediator;\n".as_bytes()).await?;
-
-    let mut lib_file = File::create("src/lib.rs").await?;
-    lib_file.write("pub mod component;\npub mod controller;\n".as_bytes()).await?;
-
-    Ok(())   
-}
-
-pub async fn check_module(prefix: &str, path: &str, name: &str) -> Result<(), std::io::Error> {
-    let mut dirs = path.trim_matches('/').split('/').collect::<Vec<&str>>();
-    let mut current = prefix.to_string();
+            let dependency = dependency.clone();
+            plan.try_modify(MANIFEST_FILE, &format!("add dependency {}", dependency.name), move |cargo_data| {
+                manifest::add_dependency(cargo_data, &dependency.name, &dependency.version, &dependency.features)
+            });
+        }
+    }
+
+    plan.create(module_file(SRC_FOLDER), "");
+    plan_provider(&mut plan);
+
+    for category in config.categories() {
+        let folder = config.folder(&category);
+        if config.is_binary(&category) {
+            plan.create_dir(&folder);
+            continue;
+        }
+
+        let modules = module_segments(&category, &folder)?;
+        plan.create_dir(&folder);
+        register_modules(&mut plan, SRC_FOLDER, &modules);
+        plan.modify(module_file(&folder), "create module", |mod_data| mod_data.to_string());
+    }
+
+    Ok(plan)
+}
+
+/// Writes the provider module and declares it in the crate root.
+fn plan_provider(plan: &mut Plan) {
+    plan.create(format!("{}/{}.rs", SRC_FOLDER, PROVIDER_MODULE), PROVIDER_CODE);
+    register_modules(plan, SRC_FOLDER, &[PROVIDER_MODULE]);
+}
+
+pub async fn init_cargo(fs: &dyn Fs, config: &Config, toolchain: &Toolchain) -> Result<()> {
+    plan_init(config, &InitOptions::default())?.apply(fs, toolchain).await
+}
+
+/// Declares the modules leading from `prefix` to the component `name` at `path`.
+pub fn check_module(plan: &mut Plan, prefix: &str, path: &str, name: &str) {
     let snake_name = pascal_to_snake(name);
+    let mut modules = path.split('/').filter(|d| !d.is_empty()).collect::<Vec<&str>>();
+    modules.push(&snake_name);
 
-    loop {
-        let mod_file = format!("{}/mod.rs", current);
-        println!("Checking file: {}", mod_file);
-        let mod_data = match tokio::fs::read_to_string(&mod_file).await {
-            Ok(f) => f,
-            Err(_) => {
-                tokio::fs::write(&mod_file, "").await?;
-                String::new()
-            }
-        };
+    register_modules(plan, prefix, &modules);
+}
 
-        let v = match dirs.first() {
-            Some(v) => *v,
-            None => &snake_name,
-        };
-        
-        let mut new_mod_data = String::new();
-        if mod_data.len() > 0 {
-            new_mod_data.push_str(&mod_data);
+/// Declares each of `modules` in the module file of its parent, starting at `dir`.
+fn register_modules(plan: &mut Plan, dir: &str, modules: &[&str]) {
+    let mut current = dir.to_string();
+    for module in modules {
+        let v = module.to_string();
+        plan.try_modify(module_file(&current), &format!("register module {}", v), move |mod_data| {
+            modfile::add_declaration(mod_data, &v)
+        });
+
+        current.push_str(&format!("/{}", module));
+    }
+}
+
+/// Removes the `[[bin]]` entry pointing at `file` from `Cargo.toml`.
+fn unregister_bin(plan: &mut Plan, file: &str) {
+    let file = file.to_string();
+    plan.try_modify(MANIFEST_FILE, &format!("unregister binary {}", file), move |cargo_data| manifest::remove_bin(cargo_data, &file));
+}
+
+pub fn register_bin(plan: &mut Plan, prefix: &str, path: &str, name: &str) {
+    let snake_name = pascal_to_snake(name);
+    let full_path = match path.trim_matches('/') {
+        "" => format!("{}/{}.rs", prefix, snake_name),
+        path => format!("{}/{}/{}.rs", prefix, path, snake_name),
+    };
+
+    plan.try_modify(MANIFEST_FILE, &format!("register binary {}", snake_name), move |cargo_data| {
+        manifest::add_bin(cargo_data, &snake_name, &full_path)
+    });
+}
+
+pub fn plan_generate(fs: &dyn Fs, config: &Config, name: &str, path: &str, category: &CategoryRef, force: bool) -> Result<Plan> {
+    if !is_identifier(name) {
+        return Err(Error::InvalidName(name.to_string()));
+    }
+    check_path(path)?;
+
+    let prefix = config.folder(category);
+    let binary = config.is_binary(category);
+    let folder_modules = match binary {
+        true => Vec::new(),
+        false => module_segments(category, &prefix)?,
+    };
+
+    let dir = match path.trim_matches('/') {
+        "" => prefix.clone(),
+        path => format!("{}/{}", prefix, path),
+    };
+    let file = format!("{}/{}.rs", dir, pascal_to_snake(name));
+
+    let exists = fs.exists(Path::new(&file))?;
+    if exists && !force {
+        return Err(Error::FileExists(file.into()));
+    }
+
+    let mut plan = Plan::new();
+    plan.create_dir(&dir);
+
+    let snake_name = pascal_to_snake(name);
+    let module_path = match binary {
+        true => String::new(),
+        false => {
+            let mut modules = vec!["crate"];
+            modules.extend(&folder_modules);
+            modules.extend(path.split('/').filter(|d| !d.is_empty()));
+            modules.push(&snake_name);
+            modules.join("::")
         }
-        if !mod_data.contains(v) {
-            new_mod_data.push_str(&format!("\npub mod {};\n", v));
+    };
+    let vars = Variables::component(config, category, path, name, &snake_name, &module_path);
+    let code = config.template(fs, category)?.render(&vars)?;
+    plan.create(file, code);
+
+    match binary {
+        true => {
+            register_bin(&mut plan, &prefix, path, name);
+            // Projects initialized before the provider existed get it with their first assembler.
+            if !fs.exists(Path::new(&format!("{}/{}.rs", SRC_FOLDER, PROVIDER_MODULE)))? {
+                plan_provider(&mut plan);
+            }
         }
+        false => {
+            register_modules(&mut plan, SRC_FOLDER, &folder_modules);
+            check_module(&mut plan, &prefix, path, name);
+        }
+    }
 
-        tokio::fs::write(&mod_file, new_mod_data).await?;
+    Ok(plan)
+}
+
+/// Plans the removal of a component: its file, its `pub mod` declaration or `[[bin]]` entry,
+/// and the modules and directories left empty by it, up to the category folder.
+pub fn plan_remove(fs: &dyn Fs, config: &Config, name: &str, path: &str, category: &CategoryRef) -> Result<Plan> {
+    check_path(path)?;
+    let prefix = config.folder(category);
+    let binary = config.is_binary(category);
+    if !binary {
+        module_segments(category, &prefix)?;
+    }
+
+    let dir = match path.trim_matches('/') {
+        "" => prefix.clone(),
+        path => format!("{}/{}", prefix, path),
+    };
+    let snake_name = pascal_to_snake(name);
+    let file = format!("{}/{}.rs", dir, snake_name);
 
-        if dirs.len() == 0 {
+    if !fs.exists(Path::new(&file))? {
+        return Err(Error::ComponentNotFound(file.into()));
+    }
+
+    let mut plan = Plan::new();
+    plan.remove_file(&file);
+    if binary {
+        unregister_bin(&mut plan, &file);
+    }
+
+    prune_modules(&mut plan, fs, &prefix, &dir, &snake_name, binary, None)?;
+
+    Ok(plan)
+}
+
+/// Unregisters `module` from the module file of `dir` after its file was removed,
+/// and removes the module files and directories that are left empty, up to `prefix`.
+/// Directories leading to `keep` are kept, since a component is about to be created there.
+fn prune_modules(plan: &mut Plan, fs: &dyn Fs, prefix: &str, dir: &str, module: &str, binary: bool, keep: Option<&str>) -> Result<()> {
+    let mut current = dir.to_string();
+    let mut removed = format!("{}.rs", module);
+    let mut module = module.to_string();
+    loop {
+        let entries = match fs.read_dir(Path::new(&current)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let others = entries.iter().filter(|e| e.name != removed && e.name != "mod.rs").count();
+        let kept = keep.is_some_and(|keep| keep == current || keep.starts_with(&format!("{}/", current)));
+
+        let mod_file = module_file(&current);
+        let declarations = match binary {
+            true => String::new(),
+            false => modfile::remove_declaration(&fs.read_to_string(Path::new(&mod_file)).unwrap_or_default(), &module),
+        };
+
+        if current == prefix || kept || others > 0 || !declarations.trim().is_empty() {
+            if !binary {
+                let m = module.clone();
+                plan.modify(mod_file, &format!("unregister module {}", m), move |mod_data| modfile::remove_declaration(mod_data, &m));
+            }
             break;
         }
 
-        let dir = dirs.remove(0);
-        current.push_str(&format!("/{}", dir));
+        if !binary {
+            plan.remove_file(mod_file);
+        }
+        plan.remove_dir(&current);
+
+        let (parent, name) = current.rsplit_once('/').expect("component directories are inside their category folder");
+        removed = name.to_string();
+        module = name.to_string();
+        current = parent.to_string();
     }
 
     Ok(())
 }
 
-pub async fn register_bin(prefix: &str, path: &str, name: &str) -> Result<(), std::io::Error> {
-    let snake_name = pascal_to_snake(name);
-    let full_path = format!("{}/{}/{}.rs", prefix, path, snake_name);
+/// Plans moving a component to `new_path` and renaming it to `new_name` in its category folder.
+/// The type is renamed in the component's file, and `use` paths and type names are rewritten
+/// in every file of the crate that refers to the component's module.
+pub fn plan_relocate(
+    fs: &dyn Fs,
+    config: &Config,
+    category: &CategoryRef,
+    (path, name): (&str, &str),
+    (new_path, new_name): (&str, &str),
+) -> Result<Plan> {
+    if !is_identifier(new_name) {
+        return Err(Error::InvalidName(new_name.to_string()));
+    }
+    check_path(path)?;
+    check_path(new_path)?;
+
+    let prefix = config.folder(category);
+    let binary = config.is_binary(category);
+    let folder_modules = match binary {
+        true => Vec::new(),
+        false => module_segments(category, &prefix)?,
+    };
 
-    let mut cargo_data = tokio::fs::read_to_string("Cargo.toml").await?;
+    let component_dir = |path: &str| match path.trim_matches('/') {
+        "" => prefix.clone(),
+        path => format!("{}/{}", prefix, path),
+    };
+    let (old_dir, new_dir) = (component_dir(path), component_dir(new_path));
+    let (old_snake, new_snake) = (pascal_to_snake(name), pascal_to_snake(new_name));
+    let old_file = format!("{}/{}.rs", old_dir, old_snake);
+    let new_file = format!("{}/{}.rs", new_dir, new_snake);
 
-    cargo_data.push_str(&format!("\n[[bin]]\nname = \"{}\"\npath = \"{}\"\n", snake_name, full_path));
+    if !fs.exists(Path::new(&old_file))? {
+        return Err(Error::ComponentNotFound(old_file.into()));
+    }
+    if old_file != new_file && fs.exists(Path::new(&new_file))? {
+        return Err(Error::FileExists(new_file.into()));
+    }
 
-    tokio::fs::write("Cargo.toml", cargo_data).await?;
+    let module_path = |path: &str, snake: &str| {
+        let mut modules = folder_modules.clone();
+        modules.extend(path.split('/').filter(|d| !d.is_empty()));
+        modules.push(snake);
+        modules.join("::")
+    };
+    let (old_module, new_module) = (module_path(path, &old_snake), module_path(new_path, &new_snake));
+    let roots = ["crate".to_string(), config.name.replace('-', "_")];
+    let old_paths = roots.iter().map(|r| format!("{}::{}", r, old_module)).collect::<Vec<_>>();
+    let new_paths = roots.iter().map(|r| format!("{}::{}", r, new_module)).collect::<Vec<_>>();
+    let (from, to) = (name.to_string(), new_name.to_string());
+
+    // Files using the component's module get the new module path and type name.
+    let rewrite = move |data: &str| {
+        if binary || !old_paths.iter().any(|p| contains_identifier(data, p)) {
+            return data.to_string();
+        }
+        let mut data = data.to_string();
+        for (old, new) in old_paths.iter().zip(&new_paths) {
+            data = replace_identifier(&data, old, new);
+        }
+        replace_identifier(&data, &from, &to)
+    };
 
-    Ok(())
+    let code = fs.read_to_string(Path::new(&old_file))?;
+    let mut plan = Plan::new();
+
+    if old_file == new_file {
+        let (from, to) = (name.to_string(), new_name.to_string());
+        plan.modify(&old_file, &format!("rename {} to {}", from, to), move |data| replace_identifier(data, &from, &to));
+    } else {
+        plan.remove_file(&old_file);
+        if binary {
+            unregister_bin(&mut plan, &old_file);
+        }
+        prune_modules(&mut plan, fs, &prefix, &old_dir, &old_snake, binary, Some(&new_dir))?;
+
+        plan.create_dir(&new_dir);
+        plan.create(&new_file, replace_identifier(&rewrite(&code), name, new_name));
+        if binary {
+            register_bin(&mut plan, &prefix, new_path, new_name);
+        } else {
+            register_modules(&mut plan, SRC_FOLDER, &folder_modules);
+            check_module(&mut plan, &prefix, new_path, new_name);
+        }
+    }
+
+    if binary {
+        return Ok(plan);
+    }
+
+    let rewrite = std::sync::Arc::new(rewrite);
+    for file in files_under(fs, SRC_FOLDER)? {
+        if file == old_file || !file.ends_with(".rs") {
+            continue;
+        }
+
+        let data = fs.read_to_string(Path::new(&file))?;
+        if rewrite(&data) != data {
+            let rewrite = rewrite.clone();
+            plan.modify(file, &format!("update references to {}", name), move |data| rewrite(data));
+        }
+    }
+
+    Ok(plan)
+}
+
+pub async fn generate_file(fs: &dyn Fs, config: &Config, name: &str, path: &str, category: &CategoryRef, force: bool) -> Result<()> {
+    plan_generate(fs, config, name, path, category, force)?.apply(fs, &Toolchain::default()).await
 }
 
-pub async fn generate_file(name: &str, path: &str, _: &str, category: &Category) -> Result<(), Box<dyn Error>> {
-    let (prefix, file) = match category {
-        Category::Service => (SERVICE_FOLDER ,format!("{}/{}", SERVICE_FOLDER, path)),
-        Category::Model => (MODEL_FOLDER ,format!("{}/{}", MODEL_FOLDER, path)),
-        Category::Contract => (CONTRACT_FOLDER ,format!("{}/{}", CONTRACT_FOLDER, path)),
-        Category::Mediator => (MEDIATOR_FOLDER ,format!("{}/{}", MEDIATOR_FOLDER, path)),
-        Category::Aggregator => (AGGREGATOR_FOLDER ,format!("{}/{}", AGGREGATOR_FOLDER, path)),
-        Category::Handler => (HANDLER_FOLDER ,format!("{}/{}", HANDLER_FOLDER, path)),
-        Category::Adapter => (ADAPTER_FOLDER ,format!("{}/{}", ADAPTER_FOLDER, path)),
-        Category::Server => (SERVER_FOLDER ,format!("{}/{}", SERVER_FOLDER, path)),
-        Category::Message => (MESSAGE_FOLDER ,format!("{}/{}", MESSAGE_FOLDER, path)),
-        Category::Protocol => (PROTOCOL_FOLDER ,format!("{}/{}", PROTOCOL_FOLDER, path)),
-        Category::State => (STATE_FOLDER ,format!("{}/{}", STATE_FOLDER, path)),
-        Category::Assembler => (ASSEMBLER_FOLDER ,format!("{}/{}", ASSEMBLER_FOLDER, path)),
+/// Identifies the component defined by `file`, at `relative` inside its category folder.
+/// Returns its path and the name of the first public type it declares, or of the file for binaries.
+pub fn find_component(fs: &dyn Fs, file: &str, relative: &str) -> Result<Option<(String, String)>> {
+    let (path, file_name) = relative.rsplit_once('/').unwrap_or(("", relative));
+    let stem = match file_name.strip_suffix(".rs") {
+        Some("mod") | Some("lib") | None => return Ok(None),
+        Some(stem) => stem,
     };
 
-    tokio::fs::create_dir_all(&file).await?;
+    let code = fs.read_to_string(Path::new(file))?;
+    let declared = code.lines().find_map(|line| {
+        let rest = ["pub struct ", "pub enum ", "pub trait "].iter().find_map(|p| line.trim_start().strip_prefix(p))?;
+        let name = rest.split(|c: char| !c.is_alphanumeric() && c != '_').next()?;
+        (!name.is_empty()).then(|| name.to_string())
+    });
 
-    let file = format!("{}/{}.rs", file, pascal_to_snake(name));
+    Ok(Some((path.to_string(), declared.unwrap_or_else(|| snake_to_pascal(stem)))))
+}
 
-    let mut file = File::create(file).await?;
+#[cfg(test)]
+mod tests {
+    use std::any::type_name;
+    use std::sync::{Arc, Mutex};
+
+    use super::provider::{BoxError, Provider, ProviderError};
+    use super::*;
+    use crate::config::Language;
+    use crate::fs::MemoryFs;
+
+    fn project() -> (MemoryFs, Config) {
+        let fs = MemoryFs::new();
+        fs.create_dir_all(Path::new(SRC_FOLDER)).unwrap();
+        fs.write(Path::new(MANIFEST_FILE), "[package]\nname = \"shop\"\nversion = \"0.1.0\"\n").unwrap();
+        fs.write(Path::new("src/lib.rs"), "").unwrap();
+        (fs, Config::new("shop".to_string(), Language::Rust))
+    }
 
-    match category {
-        Category::Service => write_struct(&mut file, name).await?,
-        Category::Model => write_struct(&mut file, name).await?,
-        Category::Contract => write_trait(&mut file, name).await?,
-        Category::Mediator => write_struct(&mut file, name).await?,
-        Category::Aggregator => write_struct(&mut file, name).await?,
-        Category::Handler => write_struct(&mut file, name).await?,
-        Category::Adapter => write_struct(&mut file, name).await?,
-        Category::Server => write_struct(&mut file, name).await?,
-        Category::Message => write_enum(&mut file, name).await?,
-        Category::Protocol => write_struct(&mut file, name).await?,
-        Category::State => write_enum(&mut file, name).await?,
-        Category::Assembler => write_assembler(&mut file).await?,
+    async fn apply(fs: &MemoryFs, plan: Result<Plan>) {
+        plan.unwrap().apply(fs, &Toolchain::default()).await.unwrap();
     }
 
-    match category {
-        Category::Assembler => register_bin(prefix, path, name).await?,
-        _ => check_module(prefix, path, name).await?,
+    fn read(fs: &MemoryFs, path: &str) -> String {
+        fs.read_to_string(Path::new(path)).unwrap()
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn generate_declares_the_component_modules() {
+        let (fs, config) = project();
+        let service = CategoryRef::from(Category::Service);
+        apply(&fs, plan_generate(&fs, &config, "Invoice", "billing", &service, false)).await;
+
+        assert!(read(&fs, "src/component/service/billing/invoice.rs").contains("pub struct Invoice {"));
+        assert_eq!(read(&fs, "src/lib.rs"), "pub mod component;\n");
+        assert_eq!(read(&fs, "src/component/mod.rs"), "pub mod service;\n");
+        assert_eq!(read(&fs, "src/component/service/mod.rs"), "pub mod billing;\n");
+        assert_eq!(read(&fs, "src/component/service/billing/mod.rs"), "pub mod invoice;\n");
+
+        let again = plan_generate(&fs, &config, "Invoice", "billing", &service, false);
+        assert!(matches!(again, Err(Error::FileExists(_))));
+    }
+
+    #[test]
+    fn component_paths_must_be_module_names() {
+        let (fs, config) = project();
+        let service = CategoryRef::from(Category::Service);
+
+        let generate = plan_generate(&fs, &config, "Thing", "my-dir", &service, false);
+        assert!(matches!(generate, Err(Error::InvalidName(segment)) if segment == "my-dir"));
+        let remove = plan_remove(&fs, &config, "Thing", "my-dir", &service);
+        assert!(matches!(remove, Err(Error::InvalidName(_))));
+        let relocate = plan_relocate(&fs, &config, &service, ("", "Thing"), ("my-dir", "Thing"));
+        assert!(matches!(relocate, Err(Error::InvalidName(_))));
+    }
+
+    #[tokio::test]
+    async fn generate_registers_assemblers_as_binaries() {
+        let (fs, config) = project();
+        let assembler = CategoryRef::from(Category::Assembler);
+        apply(&fs, plan_generate(&fs, &config, "Server", "", &assembler, false)).await;
+
+        assert!(read(&fs, "src/bin/server.rs").contains("use shop::provider::{BoxError, Provider};"));
+        assert!(read(&fs, MANIFEST_FILE).contains("[[bin]]\nname = \"server\"\npath = \"src/bin/server.rs\"\n"));
+        assert_eq!(read(&fs, "src/provider.rs"), PROVIDER_CODE);
+        assert_eq!(read(&fs, "src/lib.rs"), "pub mod provider;\n");
+    }
+
+    #[tokio::test]
+    async fn remove_prunes_modules_left_empty() {
+        let (fs, config) = project();
+        let service = CategoryRef::from(Category::Service);
+        apply(&fs, plan_generate(&fs, &config, "Invoice", "billing", &service, false)).await;
+        apply(&fs, plan_generate(&fs, &config, "Ledger", "", &service, false)).await;
+        apply(&fs, plan_remove(&fs, &config, "Invoice", "billing", &service)).await;
+
+        assert!(!fs.exists(Path::new("src/component/service/billing")).unwrap());
+        assert_eq!(read(&fs, "src/component/service/mod.rs"), "pub mod ledger;\n");
+        assert!(fs.exists(Path::new("src/component/service/ledger.rs")).unwrap());
+
+        let missing = plan_remove(&fs, &config, "Invoice", "billing", &service);
+        assert!(matches!(missing, Err(Error::ComponentNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn relocate_rewrites_uses_of_the_component() {
+        let (fs, config) = project();
+        let service = CategoryRef::from(Category::Service);
+        apply(&fs, plan_generate(&fs, &config, "Invoice", "billing", &service, false)).await;
+        apply(&fs, plan_generate(&fs, &config, "Checkout", "", &CategoryRef::from(Category::Handler), false)).await;
+        fs.write(
+            Path::new("src/controller/handler/checkout.rs"),
+            "use crate::component::service::billing::invoice::Invoice;\n\npub fn checkout() -> Invoice {\n    Invoice::new()\n}\n",
+        )
+        .unwrap();
+
+        apply(&fs, plan_relocate(&fs, &config, &service, ("billing", "Invoice"), ("accounting", "Bill"))).await;
+
+        assert!(read(&fs, "src/component/service/accounting/bill.rs").contains("pub struct Bill {"));
+        assert!(!fs.exists(Path::new("src/component/service/billing")).unwrap());
+        assert_eq!(read(&fs, "src/component/service/mod.rs"), "pub mod accounting;\n");
+        assert_eq!(read(&fs, "src/component/service/accounting/mod.rs"), "pub mod bill;\n");
+        assert_eq!(
+            read(&fs, "src/controller/handler/checkout.rs"),
+            "use crate::component::service::accounting::bill::Bill;\n\npub fn checkout() -> Bill {\n    Bill::new()\n}\n"
+        );
+    }
+
+    struct Settings(u32);
+    struct Database(u32);
+    struct Service;
+
+    #[tokio::test]
+    async fn provider_constructs_dependencies_first() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let order = order.clone();
+            move || order.lock().unwrap().push(name)
+        };
+
+        let mut provider = Provider::new();
+        let service = record("service");
+        provider.register(move |(_, _): (Arc<Database>, Arc<Settings>)| async move {
+            service();
+            Ok(Service)
+        });
+        let database = record("database");
+        provider.register(move |(config,): (Arc<Settings>,)| async move {
+            database();
+            Ok(Database(config.0 + 1))
+        });
+        let config = record("config");
+        provider.register(move |(): ()| async move {
+            config();
+            Ok(Settings(1))
+        });
+        provider.construct().await.unwrap();
+
+        assert_eq!(*order.lock().unwrap(), ["config", "database", "service"]);
+        assert_eq!(provider.get::<Database>().unwrap().0, 2);
+        assert!(provider.get::<Service>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn provider_reports_cyclic_dependencies() {
+        struct Left;
+        struct Right;
+
+        let mut provider = Provider::new();
+        provider.register(|(_,): (Arc<Right>,)| async { Ok(Left) });
+        provider.register(|(_,): (Arc<Left>,)| async { Ok(Right) });
+
+        match provider.construct().await {
+            Err(ProviderError::CyclicDependency(path)) => {
+                assert_eq!(path, [type_name::<Left>(), type_name::<Right>(), type_name::<Left>()]);
+            }
+            other => panic!("expected a cyclic dependency, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn provider_reports_missing_dependencies() {
+        let mut provider = Provider::new();
+        provider.register(|(config,): (Arc<Settings>,)| async move { Ok(Database(config.0)) });
+
+        match provider.construct().await {
+            Err(ProviderError::MissingDependency { constructor, dependency }) => {
+                assert_eq!(constructor, type_name::<Database>());
+                assert_eq!(dependency, type_name::<Settings>());
+            }
+            other => panic!("expected a missing dependency, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn provider_reports_failed_constructors() {
+        let mut provider = Provider::new();
+        provider.register(|(): ()| async { Err::<Settings, BoxError>("unreachable".into()) });
+
+        let error = provider.construct().await.unwrap_err();
+        assert!(matches!(error, ProviderError::Failed { .. }));
+        assert_eq!(error.to_string(), format!("{} failed: unreachable", type_name::<Settings>()));
+    }
+
+    #[tokio::test]
+    async fn provider_is_shared_between_tasks() {
+        let mut provider = Provider::new();
+        provider.provide(Settings(1));
+        provider.update(|(config,): (Arc<Settings>,)| Settings(config.0 + 1)).unwrap();
+        let provider = Arc::new(provider);
+
+        let task = tokio::spawn({
+            let provider = provider.clone();
+            async move { provider.run(|(config,): (Arc<Settings>,)| async move { Ok(config.0) }).await }
+        });
+
+        assert_eq!(task.await.unwrap().unwrap(), 2);
+    }
 }
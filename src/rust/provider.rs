@@ -0,0 +1,231 @@
+//! Wires the components of the project together. Constructors are registered with the types
+//! they take, and `construct` runs each of them once the values it takes exist.
+
+use std::{
+	any::{type_name, Any, TypeId},
+	collections::HashMap,
+	fmt::Display,
+	future::Future,
+	pin::Pin,
+	sync::{Arc, Mutex},
+};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+type Value = Arc<dyn Any + Send + Sync>;
+type Build = Box<dyn FnOnce(&Provider) -> Result<Pin<Box<dyn Future<Output = Result<Value, BoxError>> + Send>>, ProviderError> + Send>;
+
+/// A type known to the provider.
+#[derive(Debug, Clone, Copy)]
+pub struct Key {
+	id: TypeId,
+	name: &'static str,
+}
+
+impl Key {
+	pub fn of<T: 'static>() -> Self {
+		Self { id: TypeId::of::<T>(), name: type_name::<T>() }
+	}
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+	/// No value of the type was provided or constructed
+	NotProvided(&'static str),
+	/// A constructor takes a type that is neither provided nor constructed
+	MissingDependency { constructor: &'static str, dependency: &'static str },
+	/// Constructors that take each other's types, in dependency order
+	CyclicDependency(Vec<&'static str>),
+	/// A constructor or function returned an error
+	Failed { name: &'static str, source: BoxError },
+}
+
+impl Display for ProviderError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ProviderError::NotProvided(name) => write!(f, "not provided: {}", name),
+			ProviderError::MissingDependency { constructor, dependency } => {
+				write!(f, "the constructor of {} takes {}, which is not provided", constructor, dependency)
+			}
+			ProviderError::CyclicDependency(path) => write!(f, "cyclic dependency: {}", path.join(" -> ")),
+			ProviderError::Failed { name, source } => write!(f, "{} failed: {}", name, source),
+		}
+	}
+}
+
+impl std::error::Error for ProviderError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			ProviderError::Failed { source, .. } => Some(source.as_ref()),
+			_ => None,
+		}
+	}
+}
+
+/// The values a constructor or function takes, resolved by type: `()` or a tuple of `Arc`s.
+pub trait Dependencies: Sized + Send + 'static {
+	fn keys() -> Vec<Key>;
+
+	fn resolve(provider: &Provider) -> Result<Self, ProviderError>;
+}
+
+impl Dependencies for () {
+	fn keys() -> Vec<Key> {
+		Vec::new()
+	}
+
+	fn resolve(_: &Provider) -> Result<Self, ProviderError> {
+		Ok(())
+	}
+}
+
+macro_rules! dependencies {
+	($($t:ident),+) => {
+		impl<$($t: Send + Sync + 'static),+> Dependencies for ($(Arc<$t>,)+) {
+			fn keys() -> Vec<Key> {
+				vec![$(Key::of::<$t>()),+]
+			}
+
+			fn resolve(provider: &Provider) -> Result<Self, ProviderError> {
+				Ok(($(provider.get::<$t>()?,)+))
+			}
+		}
+	};
+}
+
+dependencies!(A);
+dependencies!(A, B);
+dependencies!(A, B, C);
+dependencies!(A, B, C, D);
+dependencies!(A, B, C, D, E);
+dependencies!(A, B, C, D, E, F);
+dependencies!(A, B, C, D, E, F, G);
+dependencies!(A, B, C, D, E, F, G, H);
+
+struct Constructor {
+	output: Key,
+	inputs: Vec<Key>,
+	build: Build,
+}
+
+/// Holds the constructed values. It can be shared between tasks once constructed, e.g. in an `Arc`.
+#[derive(Default)]
+pub struct Provider {
+	// Behind a mutex so the provider is `Sync` without requiring constructors to be.
+	constructors: Mutex<Vec<Constructor>>,
+	container: HashMap<TypeId, Value>,
+}
+
+impl Provider {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Makes `value` available to constructors and functions.
+	pub fn provide<T: Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+		self.container.insert(TypeId::of::<T>(), Arc::new(value));
+		self
+	}
+
+	/// Registers an async constructor of `T`, taking its dependencies as `()` or a tuple of `Arc`s:
+	/// `provider.register(|(db,): (Arc<Db>,)| async move { Ok(Service::new(db)) })`.
+	pub fn register<D, T, F, Fut>(&mut self, constructor: F) -> &mut Self
+	where
+		D: Dependencies,
+		T: Send + Sync + 'static,
+		F: FnOnce(D) -> Fut + Send + 'static,
+		Fut: Future<Output = Result<T, BoxError>> + Send + 'static,
+	{
+		let build: Build = Box::new(move |provider: &Provider| {
+			let future = constructor(D::resolve(provider)?);
+			Ok(Box::pin(async move { future.await.map(|value| Arc::new(value) as Value) }))
+		});
+		self.constructors().push(Constructor { output: Key::of::<T>(), inputs: D::keys(), build });
+		self
+	}
+
+	/// Returns the value of type `T`.
+	pub fn get<T: Send + Sync + 'static>(&self) -> Result<Arc<T>, ProviderError> {
+		self.container
+			.get(&TypeId::of::<T>())
+			.and_then(|value| value.clone().downcast::<T>().ok())
+			.ok_or(ProviderError::NotProvided(type_name::<T>()))
+	}
+
+	/// Runs every registered constructor once, after the constructors of the types it takes.
+	pub async fn construct(&mut self) -> Result<(), ProviderError> {
+		while !self.constructors().is_empty() {
+			let container = &self.container;
+			let ready = self
+				.constructors
+				.get_mut()
+				.unwrap_or_else(|e| e.into_inner())
+				.iter()
+				.position(|c| c.inputs.iter().all(|input| container.contains_key(&input.id)));
+			let Some(ready) = ready else {
+				return Err(self.unresolved());
+			};
+
+			let constructor = self.constructors().remove(ready);
+			let name = constructor.output.name;
+			let value = (constructor.build)(self)?.await.map_err(|source| ProviderError::Failed { name, source })?;
+			self.container.insert(constructor.output.id, value);
+		}
+
+		Ok(())
+	}
+
+	/// Calls `function` with the values it takes.
+	pub async fn run<D, R, F, Fut>(&self, function: F) -> Result<R, ProviderError>
+	where
+		D: Dependencies,
+		F: FnOnce(D) -> Fut,
+		Fut: Future<Output = Result<R, BoxError>>,
+	{
+		let name = type_name::<F>();
+		function(D::resolve(self)?).await.map_err(|source| ProviderError::Failed { name, source })
+	}
+
+	/// Replaces the value of `T` with what `function` returns for the values it takes.
+	pub fn update<D, T, F>(&mut self, function: F) -> Result<(), ProviderError>
+	where
+		D: Dependencies,
+		T: Send + Sync + 'static,
+		F: FnOnce(D) -> T,
+	{
+		let value = function(D::resolve(self)?);
+		self.provide(value);
+		Ok(())
+	}
+
+	fn constructors(&mut self) -> &mut Vec<Constructor> {
+		self.constructors.get_mut().unwrap_or_else(|e| e.into_inner())
+	}
+
+	/// Explains why none of the remaining constructors can run.
+	fn unresolved(&mut self) -> ProviderError {
+		let container = &self.container;
+		let constructors = self.constructors.get_mut().unwrap_or_else(|e| e.into_inner());
+		let waiting = |c: &Constructor| c.inputs.iter().filter(|i| !container.contains_key(&i.id)).copied().collect::<Vec<_>>();
+		let producer = |key: &Key| constructors.iter().find(|c| c.output.id == key.id);
+
+		for constructor in constructors.iter() {
+			if let Some(missing) = waiting(constructor).iter().find(|key| producer(key).is_none()) {
+				return ProviderError::MissingDependency { constructor: constructor.output.name, dependency: missing.name };
+			}
+		}
+
+		// Every constructor waits on another one, so following them must come back around.
+		let mut path = vec![&constructors[0]];
+		loop {
+			let current = path[path.len() - 1];
+			let next = waiting(current).iter().find_map(producer).expect("a waiting constructor waits on another one");
+			if let Some(start) = path.iter().position(|c| c.output.id == next.output.id) {
+				let mut cycle = path[start..].iter().map(|c| c.output.name).collect::<Vec<_>>();
+				cycle.push(next.output.name);
+				return ProviderError::CyclicDependency(cycle);
+			}
+			path.push(next);
+		}
+	}
+}
@@ -64,3 +64,44 @@ pub fn camel_to_snake(s: &str) -> String {
     }
     result
 }
+
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+/// Replaces every occurrence of `from` that is not part of a longer identifier.
+/// Only the ends of `from` that are identifier characters need a boundary, so `pkg.` matches `pkg.Type`.
+pub fn replace_identifier(text: &str, from: &str, to: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for i in identifier_matches(text, from) {
+        result.push_str(&text[last..i]);
+        result.push_str(to);
+        last = i + from.len();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Whether `text` contains `from` as a whole identifier, see [`replace_identifier`].
+pub fn contains_identifier(text: &str, from: &str) -> bool {
+    identifier_matches(text, from).next().is_some()
+}
+
+fn identifier_matches<'a>(text: &'a str, from: &'a str) -> impl Iterator<Item = usize> + 'a {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let check_start = from.starts_with(is_ident);
+    let check_end = from.ends_with(is_ident);
+
+    text.match_indices(from).map(|(i, _)| i).filter(move |&i| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + from.len()..].chars().next();
+        let joined_start = check_start && before.is_some_and(is_ident);
+        let joined_end = check_end && after.is_some_and(is_ident);
+        !joined_start && !joined_end
+    })
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    category::CategoryRef,
+    config::{Config, Language},
+    fs::Fs,
+    strfmt::{pascal_to_snake, snake_to_camel, snake_to_pascal},
+    Error, Result,
+};
+
+/// Project-local templates override the built-in ones: `.stack/templates/<language>/<category>.tmpl`.
+pub const TEMPLATE_DIR: &str = ".stack/templates";
+
+/// The values a template can refer to as `{{ variable }}`.
+#[derive(Debug, Clone, Default)]
+pub struct Variables(BTreeMap<String, String>);
+
+impl Variables {
+    /// The variables of a component: its name in every casing, its category, path, package,
+    /// module path, the project name and the name of its Rust crate.
+    pub fn component(config: &Config, category: &CategoryRef, path: &str, name: &str, package: &str, module_path: &str) -> Self {
+        let snake = pascal_to_snake(name);
+
+        let mut vars = Self::default();
+        vars.set("name", name);
+        vars.set("name_pascal", &snake_to_pascal(&snake));
+        vars.set("name_camel", &snake_to_camel(&snake));
+        vars.set("name_snake", &snake);
+        vars.set("name_upper", &snake.to_uppercase());
+        vars.set("name_lower", &name.to_lowercase());
+        vars.set("category", &category.to_string());
+        vars.set("path", path.trim_matches('/'));
+        vars.set("package", package);
+        vars.set("module_path", module_path);
+        vars.set("project", &config.name);
+        vars.set("crate_name", &config.name.replace('-', "_"));
+        vars
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.0.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    /// Where the template came from, for error messages
+    pub source: String,
+    pub text: String,
+}
+
+impl Template {
+    /// Loads the template at `path`, falling back to `builtin` when the file does not exist.
+    pub fn load(fs: &dyn Fs, path: &Path, builtin: Option<&str>) -> Result<Self> {
+        match (fs.read_to_string(path), builtin) {
+            (Ok(text), _) => Ok(Self { source: path.display().to_string(), text }),
+            (Err(e), Some(builtin)) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self {
+                source: format!("built-in template for {}", path.display()),
+                text: builtin.to_string(),
+            }),
+            (Err(e), None) if e.kind() == std::io::ErrorKind::NotFound => Err(Error::TemplateNotFound(path.to_path_buf())),
+            (Err(e), _) => Err(e.into()),
+        }
+    }
+
+    /// Replaces every `{{ variable }}` with its value. Braces that do not enclose an identifier are kept as is.
+    pub fn render(&self, vars: &Variables) -> Result<String> {
+        let mut out = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
+
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+
+            match after.find("}}").map(|end| (after[..end].trim(), end)) {
+                Some((key, end)) if is_variable(key) => {
+                    let value = vars.get(key).ok_or_else(|| Error::UnknownTemplateVariable {
+                        template: self.source.clone(),
+                        variable: key.to_string(),
+                    })?;
+                    out.push_str(value);
+                    rest = &after[end + 2..];
+                }
+                _ => {
+                    out.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+
+        Ok(out)
+    }
+}
+
+/// The project-local template of a category.
+pub fn template_path(language: &Language, category: &str) -> PathBuf {
+    Path::new(TEMPLATE_DIR).join(language.to_string()).join(format!("{}.tmpl", category))
+}
+
+fn is_variable(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> Result<String> {
+        let mut vars = Variables::default();
+        vars.set("name", "Order");
+        Template { source: "test.tmpl".to_string(), text: text.to_string() }.render(&vars)
+    }
+
+    #[test]
+    fn replaces_variables() {
+        assert_eq!(render("type {{name}} struct{}; {{ name }}").unwrap(), "type Order struct{}; Order");
+    }
+
+    #[test]
+    fn unknown_variables_name_the_template() {
+        let error = render("{{name}} {{ missing }}").unwrap_err();
+        assert!(matches!(&error, Error::UnknownTemplateVariable { template, variable } if template == "test.tmpl" && variable == "missing"));
+    }
+
+    #[test]
+    fn unclosed_braces_are_kept() {
+        assert_eq!(render("{{name}} {{name").unwrap(), "Order {{name");
+        assert_eq!(render("{{ {{name}}").unwrap(), "{{ Order");
+    }
+
+    #[test]
+    fn braces_around_other_text_are_kept() {
+        assert_eq!(render("{{}} {{.Field}} {{ .Name }}").unwrap(), "{{}} {{.Field}} {{ .Name }}");
+        assert_eq!(render("map[string]struct{}{{name}}").unwrap(), "map[string]struct{}Order");
+    }
+}
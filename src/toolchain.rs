@@ -0,0 +1,137 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    process::Command,
+};
+
+use crate::{config::ToolchainConfig, Error, Result};
+
+/// Runs the `cargo` and `go` commands the generators depend on.
+#[derive(Debug, Clone, Default)]
+pub struct Toolchain {
+    /// Stream the output of every command while it runs
+    pub verbose: bool,
+    /// The directory commands run in. Plans applied to a project on disk default to its root
+    pub dir: Option<PathBuf>,
+    /// The binary run for a program, when it is not the program found on the PATH
+    pub binaries: BTreeMap<String, PathBuf>,
+}
+
+impl Toolchain {
+    pub fn new(verbose: bool) -> Self {
+        Self { verbose, dir: None, binaries: BTreeMap::new() }
+    }
+
+    pub fn in_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Runs `binary` whenever `program` is invoked.
+    pub fn with_binary(mut self, program: &str, binary: impl Into<PathBuf>) -> Self {
+        self.binaries.insert(program.to_string(), binary.into());
+        self
+    }
+
+    /// Uses the binaries of the `toolchain` section of `stack.yaml`.
+    /// The `CARGO` and `GO` environment variables take precedence over it.
+    pub fn configured(mut self, config: &ToolchainConfig) -> Self {
+        for (program, var, configured) in [("cargo", "CARGO", &config.cargo), ("go", "GO", &config.go)] {
+            let from_env = std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from);
+            if let Some(binary) = from_env.or(configured.clone()) {
+                self = self.with_binary(program, binary);
+            }
+        }
+        self
+    }
+
+    /// The binary to start for `program`. Relative paths with a directory are relative to [`Toolchain::dir`].
+    fn binary(&self, program: &str) -> PathBuf {
+        let binary = match self.binaries.get(program) {
+            Some(binary) => binary.clone(),
+            None => return PathBuf::from(program),
+        };
+        match &self.dir {
+            Some(dir) if binary.is_relative() && binary.components().count() > 1 => dir.join(binary),
+            _ => binary,
+        }
+    }
+
+    /// Runs `program` with `args`, failing with [`Error::ToolchainFailed`] when it exits unsuccessfully.
+    pub async fn run(&self, program: &str, args: &[&str]) -> Result<()> {
+        let binary = self.binary(program);
+        let cmd = command_line(&binary, args);
+        let mut command = Command::new(&binary);
+        command.args(args).stdin(Stdio::null());
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+
+        let (status, stdout, stderr) = if self.verbose {
+            println!("> {}", cmd);
+            let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(|e| spawn_error(&binary, e))?;
+            let child_stdout = child.stdout.take().expect("stdout is piped");
+            let child_stderr = child.stderr.take().expect("stderr is piped");
+            let (stdout, stderr) = tokio::try_join!(
+                tee(child_stdout, tokio::io::stdout()),
+                tee(child_stderr, tokio::io::stderr()),
+            )?;
+            (child.wait().await?, stdout, stderr)
+        } else {
+            let output = command.output().await.map_err(|e| spawn_error(&binary, e))?;
+            (
+                output.status,
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            )
+        };
+
+        check_status(cmd, status, stdout, stderr)
+    }
+}
+
+fn spawn_error(binary: &Path, e: std::io::Error) -> Error {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => Error::ToolchainNotFound(binary.display().to_string()),
+        _ => e.into(),
+    }
+}
+
+fn check_status(cmd: String, status: ExitStatus, stdout: String, stderr: String) -> Result<()> {
+    if status.success() {
+        return Ok(());
+    }
+
+    Err(Error::ToolchainFailed { cmd, code: status.code(), stdout, stderr })
+}
+
+/// Copies `reader` line by line into `writer` and returns everything that was read.
+async fn tee(reader: impl AsyncRead + Unpin, mut writer: impl AsyncWrite + Unpin) -> std::io::Result<String> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut captured = String::new();
+    while let Some(line) = lines.next_line().await? {
+        writer.write_all(format!("{}\n", line).as_bytes()).await?;
+        writer.flush().await?;
+        captured.push_str(&line);
+        captured.push('\n');
+    }
+
+    Ok(captured)
+}
+
+fn command_line(binary: &Path, args: &[&str]) -> String {
+    let mut cmd = binary.display().to_string();
+    for arg in args {
+        if arg.is_empty() || arg.contains(char::is_whitespace) {
+            cmd.push_str(&format!(" '{}'", arg));
+        } else {
+            cmd.push_str(&format!(" {}", arg));
+        }
+    }
+    cmd
+}
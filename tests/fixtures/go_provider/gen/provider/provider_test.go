@@ -0,0 +1,340 @@
+package provider
+
+import (
+	"context"
+	"errors"
+	"reflect"
+	"testing"
+	"time"
+)
+
+type Config struct {
+	Name string
+}
+
+type Clock struct{}
+
+type Logger struct {
+	Config *Config
+}
+
+type Service struct {
+	Logger *Logger
+	Clock  *Clock
+}
+
+type Left struct{}
+
+type Right struct{}
+
+func TestZeroArgConstructor(t *testing.T) {
+	p := New()
+	if err := p.Register(func() *Config { return &Config{Name: "fixture"} }); err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	config, ok := Get[*Config](p)
+	if !ok || config.Name != "fixture" {
+		t.Fatalf("config not constructed: %v", config)
+	}
+}
+
+func TestZeroArgConstructorWithError(t *testing.T) {
+	p := New()
+	if err := p.Register(func() (*Clock, error) { return &Clock{}, nil }); err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	if _, ok := Get[*Clock](p); !ok {
+		t.Fatal("clock not constructed")
+	}
+}
+
+func TestConstructionOrder(t *testing.T) {
+	var order []string
+	p := New()
+	err := p.Register(
+		func(logger *Logger, clock *Clock) *Service {
+			order = append(order, "service")
+			return &Service{Logger: logger, Clock: clock}
+		},
+		func() *Clock {
+			order = append(order, "clock")
+			return &Clock{}
+		},
+		func(config *Config) *Logger {
+			order = append(order, "logger")
+			return &Logger{Config: config}
+		},
+		func() *Config {
+			order = append(order, "config")
+			return &Config{}
+		},
+	)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	want := []string{"config", "logger", "clock", "service"}
+	if !reflect.DeepEqual(order, want) {
+		t.Fatalf("order = %v, want %v", order, want)
+	}
+
+	service, ok := Get[*Service](p)
+	if !ok || service.Logger == nil || service.Clock == nil || service.Logger.Config == nil {
+		t.Fatalf("service not wired: %+v", service)
+	}
+}
+
+func TestConstructorRunsOnce(t *testing.T) {
+	calls := 0
+	p := New()
+	err := p.Register(
+		func() *Config { return &Config{} },
+		func() *Clock { return &Clock{} },
+		func(config *Config, clock *Clock) *Logger {
+			calls++
+			return &Logger{Config: config}
+		},
+	)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	for i := 0; i < 2; i++ {
+		if err := p.Construct(context.Background()); err != nil {
+			t.Fatal(err)
+		}
+	}
+
+	if calls != 1 {
+		t.Fatalf("constructor ran %d times", calls)
+	}
+}
+
+func TestContextIsProvided(t *testing.T) {
+	type key struct{}
+	ctx := context.WithValue(context.Background(), key{}, "fixture")
+
+	p := New()
+	err := p.Register(func(ctx context.Context) *Config {
+		return &Config{Name: ctx.Value(key{}).(string)}
+	})
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(ctx); err != nil {
+		t.Fatal(err)
+	}
+
+	if config, _ := Get[*Config](p); config == nil || config.Name != "fixture" {
+		t.Fatalf("config not constructed from context: %v", config)
+	}
+}
+
+func TestMissingDependency(t *testing.T) {
+	p := New()
+	if err := p.Register(func(config *Config) *Logger { return &Logger{Config: config} }); err != nil {
+		t.Fatal(err)
+	}
+
+	var missing ErrMissingDependency
+	if err := p.Construct(context.Background()); !errors.As(err, &missing) {
+		t.Fatalf("err = %v, want a missing dependency", err)
+	}
+	if missing.Type != reflect.TypeOf(&Config{}) {
+		t.Fatalf("missing type = %v", missing.Type)
+	}
+}
+
+func TestCyclicDependency(t *testing.T) {
+	p := New()
+	err := p.Register(
+		func(*Right) *Left { return &Left{} },
+		func(*Left) *Right { return &Right{} },
+	)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	var cycle ErrCyclicDependency
+	if err := p.Construct(context.Background()); !errors.As(err, &cycle) {
+		t.Fatalf("err = %v, want a cyclic dependency", err)
+	}
+	if len(cycle.Path) != 3 || cycle.Path[0] != cycle.Path[2] {
+		t.Fatalf("cycle path = %v", cycle.Path)
+	}
+}
+
+func TestConstructorError(t *testing.T) {
+	failure := errors.New("no config")
+	p := New()
+	if err := p.Register(func() (*Config, error) { return nil, failure }); err != nil {
+		t.Fatal(err)
+	}
+
+	if err := p.Construct(context.Background()); !errors.Is(err, failure) {
+		t.Fatalf("err = %v, want %v", err, failure)
+	}
+	if _, ok := Get[*Config](p); ok {
+		t.Fatal("config stored despite the error")
+	}
+}
+
+func TestInvalidConstructors(t *testing.T) {
+	p := New()
+
+	if err := p.Register(42); !errors.As(err, new(ErrNotAFunction)) {
+		t.Fatalf("err = %v, want not a function", err)
+	}
+	if err := p.Register(func() {}); !errors.As(err, new(ErrInvalidConstructorReturn)) {
+		t.Fatalf("err = %v, want an invalid constructor return", err)
+	}
+	if err := p.Register(func() error { return nil }); !errors.As(err, new(ErrInvalidConstructorReturn)) {
+		t.Fatalf("err = %v, want an invalid constructor return", err)
+	}
+}
+
+type recorder struct {
+	name   string
+	events *[]string
+}
+
+func (r *recorder) record(event string) error {
+	*r.events = append(*r.events, event+" "+r.name)
+	return nil
+}
+
+type Database struct{ recorder }
+
+func (d *Database) Close() error { return d.record("close") }
+
+type Worker struct{ recorder }
+
+func (w *Worker) Start(context.Context) error { return w.record("start") }
+
+func (w *Worker) Stop(context.Context) error { return w.record("stop") }
+
+type Server struct{ recorder }
+
+func (s *Server) Start(context.Context) error { return s.record("start") }
+
+func (s *Server) Stop(context.Context) error { return s.record("stop") }
+
+type Stuck struct {
+	release chan struct{}
+}
+
+func (s *Stuck) Stop(context.Context) error {
+	<-s.release
+	return nil
+}
+
+type Failing struct{}
+
+func (f *Failing) Start(context.Context) error { return errors.New("cannot start") }
+
+func registerLifecycle(t *testing.T, p *Provider, events *[]string) {
+	t.Helper()
+	err := p.Register(
+		func(*Worker) *Server { return &Server{recorder{"server", events}} },
+		func(*Database) *Worker { return &Worker{recorder{"worker", events}} },
+		func() *Database { return &Database{recorder{"database", events}} },
+	)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+}
+
+func TestLifecycleOrder(t *testing.T) {
+	var events []string
+	p := New()
+	registerLifecycle(t, p, &events)
+
+	if err := p.Start(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Stop(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	want := []string{"start worker", "start server", "stop server", "stop worker", "close database"}
+	if !reflect.DeepEqual(events, want) {
+		t.Fatalf("events = %v, want %v", events, want)
+	}
+
+	if err := p.Stop(context.Background()); err != nil || len(events) != len(want) {
+		t.Fatalf("second stop ran hooks again: %v, %v", err, events)
+	}
+}
+
+func TestStopSkipsValuesNotStarted(t *testing.T) {
+	var events []string
+	p := New()
+	registerLifecycle(t, p, &events)
+
+	if err := p.Stop(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	want := []string{"close database"}
+	if !reflect.DeepEqual(events, want) {
+		t.Fatalf("events = %v, want %v", events, want)
+	}
+}
+
+func TestStopTimeout(t *testing.T) {
+	var events []string
+	release := make(chan struct{})
+	defer close(release)
+
+	p := New()
+	err := p.Register(
+		func() *Database { return &Database{recorder{"database", &events}} },
+		func(*Database) *Stuck { return &Stuck{release: release} },
+	)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	ctx, cancel := context.WithTimeout(context.Background(), 20*time.Millisecond)
+	defer cancel()
+
+	var hook ErrHook
+	if err := p.Stop(ctx); !errors.Is(err, context.DeadlineExceeded) || !errors.As(err, &hook) || hook.Hook != "Stop" {
+		t.Fatalf("err = %v, want a timed out Stop", err)
+	}
+	if len(events) != 0 {
+		t.Fatalf("values stopped after the timeout: %v", events)
+	}
+}
+
+func TestStartFailure(t *testing.T) {
+	p := New()
+	if err := p.Register(func() *Failing { return &Failing{} }); err != nil {
+		t.Fatal(err)
+	}
+	if err := p.Construct(context.Background()); err != nil {
+		t.Fatal(err)
+	}
+
+	var hook ErrHook
+	if err := p.Start(context.Background()); !errors.As(err, &hook) || hook.Hook != "Start" {
+		t.Fatalf("err = %v, want a failed Start", err)
+	}
+}
@@ -0,0 +1,46 @@
+//! Runs the Go test suite of `tests/fixtures/go_provider` against the generated provider.
+//! It needs a Go toolchain, so it only runs with `cargo test -- --ignored`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+fn go() -> PathBuf {
+    std::env::var_os("GO").map_or_else(|| PathBuf::from("go"), PathBuf::from)
+}
+
+#[test]
+#[ignore = "needs a Go toolchain"]
+fn generated_provider_passes_go_tests() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/go_provider");
+    let project = std::env::temp_dir().join(format!("stack-go-provider-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&project);
+    copy_dir(&fixture, &project).unwrap();
+    fs::write(project.join("gen/provider/provider.go"), stack::go::PROVIDER_CODE).unwrap();
+
+    let output = Command::new(go()).args(["test", "./..."]).current_dir(&project).output();
+    let _ = fs::remove_dir_all(&project);
+    let output = output.unwrap_or_else(|e| panic!("failed to run {}: {}", go().display(), e));
+
+    assert!(
+        output.status.success(),
+        "go test failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}